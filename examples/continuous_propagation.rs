@@ -1,15 +1,15 @@
 use ndarray::Array2;
 use petgraph::Graph;
-use wwl::{KernelConfig, WWLKernel};
+use wwl::{GraphType, KernelConfig, WWLKernel};
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Create graphs without labels for continuous propagation
-    let mut graph1 = Graph::new_undirected();
+    let mut graph1: GraphType = Graph::new_undirected();
     let n1_1 = graph1.add_node(None);
     let n1_2 = graph1.add_node(None);
     graph1.add_edge(n1_1, n1_2, ());
 
-    let mut graph2 = Graph::new_undirected();
+    let mut graph2: GraphType = Graph::new_undirected();
     let n2_1 = graph2.add_node(None);
     let n2_2 = graph2.add_node(None);
     graph2.add_edge(n2_1, n2_2, ());
@@ -0,0 +1,68 @@
+"""Environment report of the `wwl` crate's Python backends.
+
+`report()` returns one tab-separated line for the interpreter, followed by
+one line per module: `module`, `found` or `missing`, then the version or the
+import error. See `src/backend/environment.rs` for the parser.
+"""
+
+import importlib
+import sys
+
+MODULES = (
+    ("wwl", "wwl"),
+    ("igraph", "igraph"),
+    ("ot", "POT"),
+    ("numpy", "numpy"),
+    ("sklearn", "scikit-learn"),
+)
+
+
+def clean(text):
+    return " ".join(str(text).split())
+
+
+def version_of(imported, distribution):
+    version = getattr(imported, "__version__", None)
+    if version is None:
+        try:
+            from importlib import metadata
+
+            version = metadata.version(distribution)
+        except Exception:
+            version = ""
+    return clean(version)
+
+
+def report():
+    lines = [
+        "python\t%d.%d.%d\t%s" % (sys.version_info[:3] + (clean(sys.executable),))
+    ]
+    for module, distribution in MODULES:
+        try:
+            imported = importlib.import_module(module)
+        except Exception as error:
+            lines.append("%s\tmissing\t%s" % (module, clean(error)))
+        else:
+            lines.append(
+                "%s\tfound\t%s" % (module, version_of(imported, distribution))
+            )
+    return "\n".join(lines)
+
+
+def add_virtualenv(root):
+    """Adds the site-packages of a virtualenv, returning whether any exists."""
+    import os
+    import site
+
+    candidates = [
+        os.path.join(root, "lib", "python%d.%d" % sys.version_info[:2], "site-packages"),
+        os.path.join(root, "Lib", "site-packages"),
+    ]
+    found = [path for path in candidates if os.path.isdir(path)]
+    for path in found:
+        site.addsitedir(path)
+    return bool(found)
+
+
+if __name__ == "__main__":
+    sys.stdout.write(report())
@@ -0,0 +1,315 @@
+//! Python environments of the Python-based backends
+//!
+//! [`Diagnostics`] report the interpreter and the packages the reference
+//! implementation depends on, and flag versions known not to work together,
+//! so that a broken environment is reported when a backend is created rather
+//! than by the first computation.
+
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::WwlError;
+
+/// Source of [`Diagnostics::parse`] reports, run inside the environment
+pub(crate) const ENVIRONMENT_SCRIPT: &str = include_str!("environment.py");
+
+/// Modules probed by the diagnostics and the packages providing them
+const PACKAGES: [(&str, &str); 5] = [
+    ("wwl", "wwl"),
+    ("igraph", "igraph"),
+    ("ot", "POT"),
+    ("numpy", "numpy"),
+    ("sklearn", "scikit-learn"),
+];
+
+/// Oldest Python version supported by the bindings
+const MINIMUM_PYTHON: [u64; 2] = [3, 7];
+
+/// Oldest versions of packages built against the numpy 2 ABI
+const NUMPY_2_MINIMUM: [(&str, [u64; 3]); 2] = [("ot", [0, 9, 4]), ("sklearn", [1, 4, 2])];
+
+/// Python installation a backend runs on
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum PythonEnvironment {
+    /// The interpreter the embedded backend was linked against, or `python3`
+    /// from `PATH` for worker processes
+    #[default]
+    Default,
+    /// Root directory of a virtualenv
+    ///
+    /// The embedded backend adds its `site-packages` to the module search
+    /// path, which requires the virtualenv to match the embedded Python
+    /// version; worker processes run its own interpreter.
+    Virtualenv(PathBuf),
+    /// Interpreter executable, for worker processes only
+    Interpreter(PathBuf),
+}
+
+impl PythonEnvironment {
+    /// Interpreter executable started for worker processes
+    pub fn interpreter(&self) -> PathBuf {
+        match self {
+            PythonEnvironment::Default => PathBuf::from("python3"),
+            PythonEnvironment::Virtualenv(root) if cfg!(windows) => {
+                root.join("Scripts").join("python.exe")
+            }
+            PythonEnvironment::Virtualenv(root) => root.join("bin").join("python"),
+            PythonEnvironment::Interpreter(path) => path.clone(),
+        }
+    }
+}
+
+/// Presence of a Python package
+#[derive(Clone, Debug, PartialEq)]
+pub enum PackageStatus {
+    /// The module was imported; the version is `None` if it is not exposed
+    Found { version: Option<String> },
+    /// Importing the module failed with this message
+    Missing { message: String },
+}
+
+/// Python package the reference implementation depends on
+#[derive(Clone, Debug, PartialEq)]
+pub struct Package {
+    /// Name of the importable module, e.g. `ot`
+    pub module: String,
+    /// Name of the distribution providing it, e.g. `POT`
+    pub name: String,
+    pub status: PackageStatus,
+}
+
+impl Package {
+    /// Version of the package, if found and exposed
+    pub fn version(&self) -> Option<&str> {
+        match &self.status {
+            PackageStatus::Found { version } => version.as_deref(),
+            PackageStatus::Missing { .. } => None,
+        }
+    }
+}
+
+/// Report on a Python environment
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostics {
+    /// Version of the interpreter, e.g. `3.11.7`
+    pub python_version: String,
+    /// Path of the interpreter executable as reported by `sys.executable`
+    pub executable: String,
+    /// `wwl`, `igraph`, `ot`, `numpy` and `sklearn`, in this order
+    pub packages: Vec<Package>,
+}
+
+impl Diagnostics {
+    /// Parses the report printed by `environment.py`
+    pub(crate) fn parse(report: &str) -> Result<Self, WwlError> {
+        let malformed =
+            || WwlError::Extraction(format!("malformed environment report: {}", report));
+        let mut lines = report
+            .lines()
+            .map(|line| line.splitn(3, '\t').collect::<Vec<_>>());
+
+        let (python_version, executable) = match lines.next().as_deref() {
+            Some(["python", version, executable]) => (version.to_string(), executable.to_string()),
+            _ => return Err(malformed()),
+        };
+        let packages = lines
+            .map(|fields| {
+                let (module, status) = match fields[..] {
+                    [module, "found", version] => (
+                        module,
+                        PackageStatus::Found {
+                            version: Some(version.to_string()).filter(|v| !v.is_empty()),
+                        },
+                    ),
+                    [module, "missing", message] => (
+                        module,
+                        PackageStatus::Missing {
+                            message: message.to_string(),
+                        },
+                    ),
+                    _ => return Err(malformed()),
+                };
+                let name = PACKAGES
+                    .iter()
+                    .find(|(known, _)| *known == module)
+                    .map_or(module, |(_, name)| name);
+                Ok(Package {
+                    module: module.to_string(),
+                    name: name.to_string(),
+                    status,
+                })
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Diagnostics {
+            python_version,
+            executable,
+            packages,
+        })
+    }
+
+    /// Package providing `module`, if it was probed
+    pub fn package(&self, module: &str) -> Option<&Package> {
+        self.packages
+            .iter()
+            .find(|package| package.module == module)
+    }
+
+    /// Problems preventing the backends from running, empty if none
+    pub fn issues(&self) -> Vec<String> {
+        let mut issues = Vec::new();
+        if numeric_version(&self.python_version) < MINIMUM_PYTHON.to_vec() {
+            issues.push(format!(
+                "Python {} is older than 3.7, the oldest supported version",
+                self.python_version
+            ));
+        }
+        for package in &self.packages {
+            if let PackageStatus::Missing { message } = &package.status {
+                issues.push(format!("{} cannot be imported: {}", package.name, message));
+            }
+        }
+
+        let numpy = self.package("numpy").and_then(Package::version);
+        if let Some(numpy) = numpy.filter(|numpy| numeric_version(numpy) >= vec![2]) {
+            for (module, minimum) in NUMPY_2_MINIMUM {
+                let Some(package) = self.package(module) else {
+                    continue;
+                };
+                let Some(version) = package.version() else {
+                    continue;
+                };
+                if numeric_version(version) < minimum.to_vec() {
+                    issues.push(format!(
+                        "{} {} does not support numpy {}; upgrade to {} {}.{}.{} or later",
+                        package.name,
+                        version,
+                        numpy,
+                        package.name,
+                        minimum[0],
+                        minimum[1],
+                        minimum[2]
+                    ));
+                }
+            }
+        }
+        issues
+    }
+
+    /// Fails if the backends cannot run in this environment
+    ///
+    /// A package that cannot be imported is reported as
+    /// [`WwlError::ModuleNotFound`], other issues as
+    /// [`WwlError::IncompatibleEnvironment`].
+    pub fn check(&self) -> Result<(), WwlError> {
+        for package in &self.packages {
+            if let PackageStatus::Missing { message } = &package.status {
+                return Err(WwlError::ModuleNotFound {
+                    module: package.module.clone(),
+                    message: message.clone(),
+                });
+            }
+        }
+        let issues = self.issues();
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(WwlError::IncompatibleEnvironment(issues))
+        }
+    }
+}
+
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Python {} ({})", self.python_version, self.executable)?;
+        for package in &self.packages {
+            match &package.status {
+                PackageStatus::Found { version } => writeln!(
+                    f,
+                    "  {}: {}",
+                    package.name,
+                    version.as_deref().unwrap_or("unknown version")
+                )?,
+                PackageStatus::Missing { .. } => writeln!(f, "  {}: missing", package.name)?,
+            }
+        }
+        for issue in self.issues() {
+            writeln!(f, "Issue: {}", issue)?;
+        }
+        Ok(())
+    }
+}
+
+/// Leading numeric components of a version, e.g. `[0, 9, 4]` for
+/// `0.9.4.post1` and `[2, 0, 0]` for `2.0.0rc1`
+fn numeric_version(version: &str) -> Vec<u64> {
+    version
+        .split('.')
+        .map_while(|component| {
+            let digits: String = component.chars().take_while(char::is_ascii_digit).collect();
+            digits.parse().ok()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(numpy: &str, pot: &str) -> String {
+        [
+            "python\t3.11.7\t/usr/bin/python3".to_string(),
+            "wwl\tfound\t".to_string(),
+            "igraph\tfound\t0.11.8".to_string(),
+            format!("ot\tfound\t{}", pot),
+            format!("numpy\tfound\t{}", numpy),
+            "sklearn\tfound\t1.5.0".to_string(),
+        ]
+        .join("\n")
+    }
+
+    #[test]
+    fn test_parse_report() {
+        let diagnostics = Diagnostics::parse(&report("1.26.4", "0.9.1")).unwrap();
+        assert_eq!(diagnostics.python_version, "3.11.7");
+        assert_eq!(diagnostics.packages.len(), 5);
+        assert_eq!(diagnostics.package("ot").unwrap().name, "POT");
+        assert_eq!(diagnostics.package("wwl").unwrap().version(), None);
+        assert!(diagnostics.issues().is_empty());
+        assert!(diagnostics.check().is_ok());
+
+        assert!(Diagnostics::parse("not a report").is_err());
+    }
+
+    #[test]
+    fn test_known_incompatibilities() {
+        let diagnostics = Diagnostics::parse(&report("2.0.1", "0.9.3")).unwrap();
+        let issues = diagnostics.issues();
+        assert_eq!(issues.len(), 1);
+        assert!(issues[0].starts_with("POT 0.9.3 does not support numpy 2.0.1"));
+        assert!(matches!(
+            diagnostics.check(),
+            Err(WwlError::IncompatibleEnvironment(_))
+        ));
+
+        let diagnostics = Diagnostics::parse(&report("2.0.1", "0.9.4.post1")).unwrap();
+        assert!(diagnostics.issues().is_empty());
+
+        let missing = report("1.26.4", "0.9.4").replace("wwl\tfound\t", "wwl\tmissing\tNo module");
+        assert!(matches!(
+            Diagnostics::parse(&missing).unwrap().check(),
+            Err(WwlError::ModuleNotFound { module, .. }) if module == "wwl"
+        ));
+    }
+
+    #[test]
+    fn test_numeric_version() {
+        assert_eq!(numeric_version("0.9.4.post1"), vec![0, 9, 4]);
+        assert_eq!(numeric_version("2.0.0rc1"), vec![2, 0, 0]);
+        assert_eq!(numeric_version("dev"), Vec::<u64>::new());
+        assert_eq!(
+            PythonEnvironment::Interpreter(PathBuf::from("/opt/python")).interpreter(),
+            PathBuf::from("/opt/python")
+        );
+    }
+}
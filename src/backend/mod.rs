@@ -0,0 +1,211 @@
+//! Computation backends
+//!
+//! A [`Backend`] runs the embedding and transport stages behind
+//! [`WWLKernel`](crate::WWLKernel). Which implementations are compiled is
+//! controlled by the `python`, `native` and `worker` cargo features.
+
+use ndarray::{s, Array2};
+
+use crate::transport::Transport;
+use crate::{DistanceConfig, KernelConfig, WlGraph, WwlError};
+
+#[cfg(any(feature = "python", feature = "worker"))]
+mod environment;
+#[cfg(feature = "native")]
+mod native;
+#[cfg(feature = "python")]
+mod python;
+#[cfg(any(feature = "python", feature = "worker"))]
+mod reference;
+#[cfg(feature = "worker")]
+mod worker;
+
+#[cfg(any(feature = "python", feature = "worker"))]
+pub use environment::{Diagnostics, Package, PackageStatus, PythonEnvironment};
+#[cfg(feature = "native")]
+pub use native::NativeBackend;
+#[cfg(feature = "python")]
+pub use python::PythonBackend;
+#[cfg(feature = "worker")]
+pub use worker::{WorkerBackend, WorkerConfig};
+
+/// Engine computing WWL kernel and distance matrices
+///
+/// Node features, when given, hold one `node_count × dims` matrix per graph
+/// and have already been validated against `graphs` by the caller. Missing
+/// labels are resolved before graphs reach a backend, so configurations come
+/// without their node label type.
+///
+/// Cross computations take the training graphs followed by the test graphs
+/// in one slice, so that both share one label space; `split` is the number
+/// of training graphs. They return one row per test graph and one column per
+/// training graph. The provided implementations compute the full square
+/// matrix and slice it.
+///
+/// Backends are shared between threads through [`WWLKernel`](crate::WWLKernel)
+/// and must therefore be `Send + Sync`.
+pub trait Backend: Send + Sync {
+    /// Computes the WWL kernel matrix
+    fn compute_kernel(
+        &self,
+        graphs: &[WlGraph],
+        node_features: Option<&[Array2<f64>]>,
+        config: &KernelConfig<()>,
+    ) -> Result<Array2<f64>, WwlError>;
+
+    /// Computes pairwise Wasserstein distances
+    fn compute_distance(
+        &self,
+        graphs: &[WlGraph],
+        node_features: Option<&[Array2<f64>]>,
+        config: &DistanceConfig<()>,
+    ) -> Result<Array2<f64>, WwlError>;
+
+    /// Computes pairwise Wasserstein distances for every number of iterations
+    ///
+    /// Entry `h` of the result is the distance matrix obtained with `h`
+    /// iterations, for `h = 0..=config.num_iterations`. The provided
+    /// implementation runs [`Backend::compute_distance`] once per `h`.
+    fn compute_distance_per_iteration(
+        &self,
+        graphs: &[WlGraph],
+        node_features: Option<&[Array2<f64>]>,
+        config: &DistanceConfig<()>,
+    ) -> Result<Vec<Array2<f64>>, WwlError> {
+        (0..=config.num_iterations)
+            .map(|num_iterations| {
+                let config = DistanceConfig {
+                    num_iterations,
+                    ..config.clone()
+                };
+                self.compute_distance(graphs, node_features, &config)
+            })
+            .collect()
+    }
+
+    /// Computes the Wasserstein distance between two graphs
+    ///
+    /// The provided implementation computes the `2 × 2` distance matrix.
+    fn compute_pair_distance(
+        &self,
+        graphs: &[WlGraph; 2],
+        node_features: Option<&[Array2<f64>; 2]>,
+        config: &DistanceConfig<()>,
+    ) -> Result<f64, WwlError> {
+        let features = node_features.map(|features| &features[..]);
+        Ok(self.compute_distance(graphs, features, config)?[[0, 1]])
+    }
+
+    /// Solves the transport problem between two graphs, keeping the plan
+    ///
+    /// Backends that only report distances return [`WwlError::Unsupported`],
+    /// which the provided implementation does.
+    fn compute_pair_transport(
+        &self,
+        graphs: &[WlGraph; 2],
+        node_features: Option<&[Array2<f64>; 2]>,
+        config: &DistanceConfig<()>,
+    ) -> Result<Transport, WwlError> {
+        let _ = (graphs, node_features, config);
+        Err(WwlError::Unsupported(
+            "transport plans require the native backend".to_string(),
+        ))
+    }
+
+    /// Computes Wasserstein distances from test graphs to training graphs
+    fn compute_cross_distance(
+        &self,
+        graphs: &[WlGraph],
+        split: usize,
+        node_features: Option<&[Array2<f64>]>,
+        config: &DistanceConfig<()>,
+    ) -> Result<Array2<f64>, WwlError> {
+        let distances = self.compute_distance(graphs, node_features, config)?;
+        Ok(distances.slice(s![split.., ..split]).to_owned())
+    }
+
+    /// Computes the WWL kernel between test graphs and training graphs
+    ///
+    /// Each graph is represented by its distances to the training graphs, so
+    /// that the cross kernel of the training graphs with themselves equals
+    /// their square kernel matrix.
+    fn compute_cross_kernel(
+        &self,
+        graphs: &[WlGraph],
+        split: usize,
+        node_features: Option<&[Array2<f64>]>,
+        config: &KernelConfig<()>,
+    ) -> Result<Array2<f64>, WwlError> {
+        let distances =
+            self.compute_distance(graphs, node_features, &kernel_distance_config(config))?;
+        Ok(laplacian_kernel(
+            &distances.slice(s![split.., ..split]).to_owned(),
+            &distances.slice(s![..split, ..split]).to_owned(),
+            config.gamma,
+        ))
+    }
+}
+
+/// Distance configuration matching a kernel configuration
+pub(crate) fn kernel_distance_config<L: Clone>(config: &KernelConfig<L>) -> DistanceConfig<L> {
+    DistanceConfig {
+        num_iterations: config.num_iterations,
+        sinkhorn: config.sinkhorn,
+        sinkhorn_config: config.sinkhorn_config.clone(),
+        enforce_continuous: false,
+        missing_labels: config.missing_labels.clone(),
+        num_threads: config.num_threads,
+    }
+}
+
+/// Initial labels as one-column node features, as continuous propagation
+/// reads labeled graphs under [`DistanceConfig::enforce_continuous`]
+#[cfg(any(feature = "native", feature = "python", feature = "worker"))]
+pub(crate) fn labels_as_features(graphs: &[WlGraph]) -> Result<Vec<Array2<f64>>, WwlError> {
+    graphs
+        .iter()
+        .enumerate()
+        .map(|(index, graph)| {
+            let features = graph
+                .label_features()
+                .map_err(|node| WwlError::NonNumericLabel {
+                    graph: index,
+                    node: graph.node_index(node),
+                })?;
+            Ok(Array2::from_shape_vec((graph.node_count(), 1), features)
+                .expect("one feature per node"))
+        })
+        .collect()
+}
+
+/// Entry `[0, 1]` of [`laplacian_kernel`] on the distance matrix of two
+/// graphs at Wasserstein distance `distance`
+///
+/// Their rows `[0, d]` and `[d, 0]` lie `2d` apart and `gamma` defaults to
+/// 1/2, so the default is the laplacian kernel `exp(-d)` of the WWL paper.
+pub(crate) fn pair_kernel(distance: f64, gamma: Option<f64>) -> f64 {
+    let distances = ndarray::array![[0.0, distance], [distance, 0.0]];
+    laplacian_kernel(&distances, &distances, gamma)[[0, 1]]
+}
+
+/// Laplacian kernel between the rows of two distance matrices
+///
+/// Matches `sklearn.metrics.pairwise.laplacian_kernel(X, Y, gamma)` as used by
+/// the reference implementation, including its default `gamma = 1 / n` for
+/// rows of `n` distances.
+pub(crate) fn laplacian_kernel(
+    rows: &Array2<f64>,
+    columns: &Array2<f64>,
+    gamma: Option<f64>,
+) -> Array2<f64> {
+    let gamma = gamma.unwrap_or(1.0 / rows.ncols().max(1) as f64);
+    Array2::from_shape_fn((rows.nrows(), columns.nrows()), |(i, j)| {
+        let manhattan: f64 = rows
+            .row(i)
+            .iter()
+            .zip(columns.row(j))
+            .map(|(a, b)| (a - b).abs())
+            .sum();
+        (-gamma * manhattan).exp()
+    })
+}
@@ -0,0 +1,619 @@
+//! Pure Rust backend
+
+use std::sync::{Arc, Mutex};
+
+use ndarray::parallel::prelude::*;
+use ndarray::{s, Array2, Axis};
+use rayon::{ThreadPool, ThreadPoolBuilder};
+
+use super::{kernel_distance_config, labels_as_features, laplacian_kernel, Backend};
+use crate::cache::EmbeddingCache;
+use crate::propagation::{categorical_propagation, continuous_propagation};
+use crate::transport::{emd, euclidean_cost, hamming_cost, sinkhorn, Convergence, Transport};
+use crate::{DistanceConfig, KernelConfig, SinkhornConfig, WlGraph, WwlError};
+
+/// Backend running propagation and optimal transport natively
+///
+/// Mirrors the reference Python implementation: categorical embeddings are
+/// compared with the hamming ground cost, continuous ones with the euclidean
+/// ground cost, and the kernel is a laplacian kernel on the distance matrix.
+///
+/// Distance matrices are computed in parallel on `config.num_threads`
+/// threads, or on the global rayon pool when it is `None`. The pool of the
+/// last thread count used is kept for later calls, shared with clones of the
+/// backend; a call with another count replaces it. With `sinkhorn` and
+/// [`SinkhornConfig::require_convergence`], a pair that does not converge
+/// fails the call with [`WwlError::NotConverged`].
+///
+/// With an [`EmbeddingCache`], graphs embedded by earlier calls are not
+/// propagated again. Pairs and transport plans bypass the cache, as does
+/// `enforce_continuous` on labeled graphs.
+#[derive(Clone, Debug, Default)]
+pub struct NativeBackend {
+    cache: Option<EmbeddingCache>,
+    pool: Arc<Mutex<Option<Arc<ThreadPool>>>>,
+}
+
+impl NativeBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a backend looking embeddings up in `cache` first
+    pub fn with_cache(cache: EmbeddingCache) -> Self {
+        NativeBackend {
+            cache: Some(cache),
+            ..Self::default()
+        }
+    }
+
+    /// Cache of the backend, if any
+    pub fn cache(&self) -> Option<&EmbeddingCache> {
+        self.cache.as_ref()
+    }
+
+    /// Runs `work` on a pool of `num_threads` threads, or on the global rayon
+    /// pool if `None`
+    fn with_threads<T: Send>(
+        &self,
+        num_threads: Option<usize>,
+        work: impl FnOnce() -> T + Send,
+    ) -> Result<T, WwlError> {
+        match num_threads {
+            None => Ok(work()),
+            Some(num_threads) => Ok(self.pool(num_threads)?.install(work)),
+        }
+    }
+
+    /// Pool of `num_threads` threads, replacing the kept pool if it has
+    /// another size
+    ///
+    /// A replaced pool shuts down once the calls still running on it finish.
+    fn pool(&self, num_threads: usize) -> Result<Arc<ThreadPool>, WwlError> {
+        let mut kept = self
+            .pool
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(pool) = kept.as_ref() {
+            if pool.current_num_threads() == num_threads {
+                return Ok(pool.clone());
+            }
+        }
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(num_threads)
+            .build()
+            .map_err(|err| {
+                WwlError::InvalidConfig(format!(
+                    "cannot start {} worker threads: {}",
+                    num_threads, err
+                ))
+            })?;
+        let pool = Arc::new(pool);
+        *kept = Some(pool.clone());
+        Ok(pool)
+    }
+
+    /// Pairwise Wasserstein distances between categorical embeddings, as for a
+    /// collection embedded by [`Backend::compute_distance`]
+    pub(crate) fn embedding_distances(
+        &self,
+        embeddings: &[Array2<usize>],
+        config: &DistanceConfig<()>,
+    ) -> Result<Array2<f64>, WwlError> {
+        let solver = Solver::from_config(config);
+        self.with_threads(config.num_threads, || {
+            pairwise_distances(embeddings.len(), |i, j| {
+                let cost = hamming_cost(&embeddings[i], &embeddings[j]);
+                Ok(solver.solve_pair(&cost, (i, j))?.distance)
+            })
+        })?
+    }
+
+    /// Wasserstein distances from `test` (rows) to `train` (columns)
+    /// embeddings; pairs reported by [`WwlError::NotConverged`] count training
+    /// embeddings first
+    pub(crate) fn embedding_cross_distances(
+        &self,
+        train: &[Array2<usize>],
+        test: &[Array2<usize>],
+        config: &DistanceConfig<()>,
+    ) -> Result<Array2<f64>, WwlError> {
+        let solver = Solver::from_config(config);
+        let split = train.len();
+        self.with_threads(config.num_threads, || {
+            cross_distances(split + test.len(), split, |i, j| {
+                let cost = hamming_cost(&test[i - split], &train[j]);
+                Ok(solver.solve_pair(&cost, (i, j))?.distance)
+            })
+        })?
+    }
+
+    /// Embeds a collection, through the cache if there is one
+    fn embed(
+        &self,
+        graphs: &[WlGraph],
+        node_features: Option<&[Array2<f64>]>,
+        config: &DistanceConfig<()>,
+    ) -> Result<Embeddings, WwlError> {
+        let Some(cache) = &self.cache else {
+            return Embeddings::new(graphs, node_features, config);
+        };
+        Ok(match node_features {
+            None if !config.enforce_continuous => {
+                Embeddings::Hashed(cache.categorical(graphs, config.num_iterations)?)
+            }
+            Some(features) => {
+                Embeddings::Continuous(cache.continuous(graphs, features, config.num_iterations)?)
+            }
+            None => Embeddings::new(graphs, node_features, config)?,
+        })
+    }
+}
+
+impl Backend for NativeBackend {
+    fn compute_kernel(
+        &self,
+        graphs: &[WlGraph],
+        node_features: Option<&[Array2<f64>]>,
+        config: &KernelConfig<()>,
+    ) -> Result<Array2<f64>, WwlError> {
+        let distances =
+            self.compute_distance(graphs, node_features, &kernel_distance_config(config))?;
+        Ok(laplacian_kernel(&distances, &distances, config.gamma))
+    }
+
+    fn compute_distance(
+        &self,
+        graphs: &[WlGraph],
+        node_features: Option<&[Array2<f64>]>,
+        config: &DistanceConfig<()>,
+    ) -> Result<Array2<f64>, WwlError> {
+        let solver = Solver::from_config(config);
+        let embeddings = self.embed(graphs, node_features, config)?;
+        self.with_threads(config.num_threads, || {
+            pairwise_distances(graphs.len(), |i, j| embeddings.distance(i, j, &solver))
+        })?
+    }
+
+    fn compute_distance_per_iteration(
+        &self,
+        graphs: &[WlGraph],
+        node_features: Option<&[Array2<f64>]>,
+        config: &DistanceConfig<()>,
+    ) -> Result<Vec<Array2<f64>>, WwlError> {
+        let solver = Solver::from_config(config);
+        // Propagate once; the embedding after `h` iterations is a prefix of
+        // the full one
+        let embeddings = self.embed(graphs, node_features, config)?;
+        self.with_threads(config.num_threads, || {
+            (0..=config.num_iterations)
+                .map(|h| {
+                    let embeddings = embeddings.truncate(h, config.num_iterations);
+                    pairwise_distances(graphs.len(), |i, j| embeddings.distance(i, j, &solver))
+                })
+                .collect()
+        })?
+    }
+
+    fn compute_pair_distance(
+        &self,
+        graphs: &[WlGraph; 2],
+        node_features: Option<&[Array2<f64>; 2]>,
+        config: &DistanceConfig<()>,
+    ) -> Result<f64, WwlError> {
+        let solver = Solver::from_config(config);
+        let features = node_features.map(|features| &features[..]);
+        let embeddings = Embeddings::new(graphs, features, config)?;
+        embeddings.distance(0, 1, &solver)
+    }
+
+    fn compute_pair_transport(
+        &self,
+        graphs: &[WlGraph; 2],
+        node_features: Option<&[Array2<f64>; 2]>,
+        config: &DistanceConfig<()>,
+    ) -> Result<Transport, WwlError> {
+        let solver = Solver::from_config(config);
+        let features = node_features.map(|features| &features[..]);
+        let embeddings = Embeddings::new(graphs, features, config)?;
+        embeddings.transport(0, 1, &solver)
+    }
+
+    fn compute_cross_distance(
+        &self,
+        graphs: &[WlGraph],
+        split: usize,
+        node_features: Option<&[Array2<f64>]>,
+        config: &DistanceConfig<()>,
+    ) -> Result<Array2<f64>, WwlError> {
+        let solver = Solver::from_config(config);
+        let embeddings = self.embed(graphs, node_features, config)?;
+        self.with_threads(config.num_threads, || {
+            cross_distances(graphs.len(), split, |i, j| {
+                embeddings.distance(i, j, &solver)
+            })
+        })?
+    }
+
+    fn compute_cross_kernel(
+        &self,
+        graphs: &[WlGraph],
+        split: usize,
+        node_features: Option<&[Array2<f64>]>,
+        config: &KernelConfig<()>,
+    ) -> Result<Array2<f64>, WwlError> {
+        let distance_config = kernel_distance_config(config);
+        let solver = Solver::from_config(&distance_config);
+        let embeddings = self.embed(graphs, node_features, &distance_config)?;
+        let distance = |i, j| embeddings.distance(i, j, &solver);
+
+        // Test graphs are only compared with training graphs
+        let (train, cross) = self.with_threads(config.num_threads, || {
+            Ok::<_, WwlError>((
+                pairwise_distances(split, distance)?,
+                cross_distances(graphs.len(), split, distance)?,
+            ))
+        })??;
+        Ok(laplacian_kernel(&cross, &train, config.gamma))
+    }
+}
+
+/// WL embeddings of a collection, compared with the matching ground cost
+enum Embeddings {
+    Categorical(Vec<Array2<usize>>),
+    /// Categorical embeddings with hashed label ids, as cached
+    Hashed(Vec<Array2<u64>>),
+    Continuous(Vec<Array2<f64>>),
+}
+
+impl Embeddings {
+    fn new(
+        graphs: &[WlGraph],
+        node_features: Option<&[Array2<f64>]>,
+        config: &DistanceConfig<()>,
+    ) -> Result<Self, WwlError> {
+        Ok(match node_features {
+            None if !config.enforce_continuous => {
+                Embeddings::Categorical(categorical_propagation(graphs, config.num_iterations))
+            }
+            Some(features) => Embeddings::Continuous(continuous_propagation(
+                graphs,
+                features,
+                config.num_iterations,
+            )),
+            None => Embeddings::Continuous(continuous_propagation(
+                graphs,
+                &labels_as_features(graphs)?,
+                config.num_iterations,
+            )),
+        })
+    }
+
+    /// Embeddings after `h` of the `num_iterations` iterations they were
+    /// propagated for
+    fn truncate(&self, h: usize, num_iterations: usize) -> Self {
+        fn prefix<T: Clone>(
+            embeddings: &[Array2<T>],
+            h: usize,
+            num_iterations: usize,
+        ) -> Vec<Array2<T>> {
+            embeddings
+                .iter()
+                .map(|embedding| {
+                    let width = embedding.ncols() / (num_iterations + 1);
+                    embedding.slice(s![.., ..width * (h + 1)]).to_owned()
+                })
+                .collect()
+        }
+
+        match self {
+            Embeddings::Categorical(embeddings) => {
+                Embeddings::Categorical(prefix(embeddings, h, num_iterations))
+            }
+            Embeddings::Hashed(embeddings) => {
+                Embeddings::Hashed(prefix(embeddings, h, num_iterations))
+            }
+            Embeddings::Continuous(embeddings) => {
+                Embeddings::Continuous(prefix(embeddings, h, num_iterations))
+            }
+        }
+    }
+
+    /// Wasserstein distance between graphs `i` and `j`
+    fn distance(&self, i: usize, j: usize, solver: &Solver) -> Result<f64, WwlError> {
+        Ok(self.transport(i, j, solver)?.distance)
+    }
+
+    /// Transport problem between graphs `i` and `j`, solved
+    fn transport(&self, i: usize, j: usize, solver: &Solver) -> Result<Transport, WwlError> {
+        let cost = match self {
+            Embeddings::Categorical(embeddings) => hamming_cost(&embeddings[i], &embeddings[j]),
+            Embeddings::Hashed(embeddings) => hamming_cost(&embeddings[i], &embeddings[j]),
+            Embeddings::Continuous(embeddings) => euclidean_cost(&embeddings[i], &embeddings[j]),
+        };
+        solver.solve_pair(&cost, (i, j))
+    }
+}
+
+/// Transport solver selected by a distance configuration
+enum Solver<'a> {
+    Exact,
+    Sinkhorn(&'a SinkhornConfig),
+}
+
+impl<'a> Solver<'a> {
+    fn from_config(config: &'a DistanceConfig<()>) -> Self {
+        if config.sinkhorn {
+            Solver::Sinkhorn(&config.sinkhorn_config)
+        } else {
+            Solver::Exact
+        }
+    }
+
+    /// Solves a transport problem, failing with the convergence report of
+    /// a Sinkhorn run that stopped early if the configuration requires
+    /// convergence
+    fn solve(&self, cost: &Array2<f64>) -> Result<Transport, Convergence> {
+        match self {
+            Solver::Exact => Ok(emd(cost)),
+            Solver::Sinkhorn(config) => match sinkhorn(cost, config) {
+                (transport, convergence)
+                    if convergence.converged || !config.require_convergence =>
+                {
+                    Ok(transport)
+                }
+                (_, convergence) => Err(convergence),
+            },
+        }
+    }
+
+    /// Solves the transport problem between `graphs`
+    fn solve_pair(
+        &self,
+        cost: &Array2<f64>,
+        graphs: (usize, usize),
+    ) -> Result<Transport, WwlError> {
+        self.solve(cost)
+            .map_err(|convergence| WwlError::NotConverged {
+                graphs,
+                iterations: convergence.iterations,
+                error: convergence.error,
+            })
+    }
+}
+
+/// Fills a symmetric `n × n` distance matrix, solving each unordered pair once
+///
+/// Rows of the upper triangle are solved in parallel, then mirrored.
+fn pairwise_distances(
+    n: usize,
+    distance: impl Fn(usize, usize) -> Result<f64, WwlError> + Sync,
+) -> Result<Array2<f64>, WwlError> {
+    let mut distances = Array2::zeros((n, n));
+    distances
+        .axis_iter_mut(Axis(0))
+        .into_par_iter()
+        .enumerate()
+        .try_for_each(|(i, mut row)| {
+            for j in i + 1..n {
+                row[j] = distance(i, j)?;
+            }
+            Ok::<_, WwlError>(())
+        })?;
+    for i in 0..n {
+        for j in 0..i {
+            distances[[i, j]] = distances[[j, i]];
+        }
+    }
+    Ok(distances)
+}
+
+/// Fills the distances from graphs `split..n` (rows) to graphs `0..split`
+/// (columns), in parallel
+fn cross_distances(
+    n: usize,
+    split: usize,
+    distance: impl Fn(usize, usize) -> Result<f64, WwlError> + Sync,
+) -> Result<Array2<f64>, WwlError> {
+    let mut distances = Array2::zeros((n - split, split));
+    distances
+        .axis_iter_mut(Axis(0))
+        .into_par_iter()
+        .enumerate()
+        .try_for_each(|(i, mut row)| {
+            for (j, d) in row.iter_mut().enumerate() {
+                *d = distance(split + i, j)?;
+            }
+            Ok::<_, WwlError>(())
+        })?;
+    Ok(distances)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::pair_kernel;
+    use crate::{intern_graphs, GraphType, MissingLabelStrategy};
+    use petgraph::Graph;
+
+    fn reference_graphs() -> Vec<WlGraph> {
+        let mut graph1: GraphType = Graph::new_undirected();
+        let n1 = graph1.add_node(Some(1));
+        let n2 = graph1.add_node(Some(2));
+        graph1.add_edge(n1, n2, ());
+
+        let mut graph2 = Graph::new_undirected();
+        let n3 = graph2.add_node(Some(1));
+        let n4 = graph2.add_node(Some(2));
+        let n5 = graph2.add_node(Some(3));
+        graph2.add_edge(n3, n4, ());
+        graph2.add_edge(n4, n5, ());
+
+        intern_graphs(&[graph1, graph2], &MissingLabelStrategy::Error)
+            .unwrap()
+            .0
+    }
+
+    #[test]
+    fn test_matches_python_reference() {
+        let backend = NativeBackend::new();
+        let graphs = reference_graphs();
+
+        let distances = backend
+            .compute_distance(&graphs, None, &DistanceConfig::default())
+            .unwrap();
+        let kernel = backend
+            .compute_kernel(&graphs, None, &KernelConfig::default())
+            .unwrap();
+
+        assert!((distances[[0, 1]] - 0.75).abs() < 1e-6);
+        assert!((kernel[[0, 1]] - 0.47236655).abs() < 1e-6);
+        assert_eq!(distances[[0, 0]], 0.0);
+        assert_eq!(kernel[[1, 1]], 1.0);
+    }
+
+    #[test]
+    fn test_continuous_and_sinkhorn() {
+        let backend = NativeBackend::new();
+        let graphs = reference_graphs();
+        let features = vec![
+            ndarray::array![[1.0, 0.5], [2.0, 0.5]],
+            ndarray::array![[1.0, 0.5], [2.0, 0.5], [3.0, 0.5]],
+        ];
+
+        let exact = backend
+            .compute_distance(&graphs, Some(&features), &DistanceConfig::default())
+            .unwrap();
+        let config = DistanceConfig {
+            sinkhorn: true,
+            sinkhorn_config: SinkhornConfig {
+                max_iterations: 1000,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let regularised = backend
+            .compute_distance(&graphs, Some(&features), &config)
+            .unwrap();
+
+        assert!(exact[[0, 1]] > 0.0);
+        assert_eq!(exact[[0, 1]], exact[[1, 0]]);
+        assert!(regularised[[0, 1]] >= exact[[0, 1]] - 1e-9);
+    }
+
+    #[test]
+    fn test_cross_matches_square() {
+        let backend = NativeBackend::new();
+        let mut graphs = reference_graphs();
+        graphs.extend(reference_graphs());
+        graphs.swap(2, 3);
+
+        let square = backend
+            .compute_distance(&graphs, None, &DistanceConfig::default())
+            .unwrap();
+        let cross = backend
+            .compute_cross_distance(&graphs, 2, None, &DistanceConfig::default())
+            .unwrap();
+        assert_eq!(cross, square.slice(ndarray::s![2.., ..2]));
+        assert_eq!(cross[[1, 0]], 0.0);
+        assert!((cross[[0, 0]] - 0.75).abs() < 1e-6);
+
+        // Against themselves, training graphs reproduce the square kernel
+        let train = reference_graphs();
+        let mut doubled = train.clone();
+        doubled.extend(train.clone());
+        let kernel = backend
+            .compute_kernel(&train, None, &KernelConfig::default())
+            .unwrap();
+        let cross_kernel = backend
+            .compute_cross_kernel(&doubled, 2, None, &KernelConfig::default())
+            .unwrap();
+        assert_eq!(cross_kernel, kernel);
+    }
+
+    #[test]
+    fn test_pair_distance() {
+        let backend = NativeBackend::new();
+        let graphs = reference_graphs();
+        let pair = [graphs[0].clone(), graphs[1].clone()];
+
+        let distance = backend
+            .compute_pair_distance(&pair, None, &DistanceConfig::default())
+            .unwrap();
+        assert!((distance - 0.75).abs() < 1e-6);
+        assert!((pair_kernel(distance, None) - 0.47236655).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_thread_count_does_not_change_distances() {
+        let backend = NativeBackend::new();
+        let mut graphs = reference_graphs();
+        graphs.extend(reference_graphs());
+        graphs.extend(reference_graphs());
+
+        let serial = backend
+            .compute_distance(
+                &graphs,
+                None,
+                &DistanceConfig {
+                    num_threads: Some(1),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        let parallel = backend
+            .compute_distance(
+                &graphs,
+                None,
+                &DistanceConfig {
+                    num_threads: Some(4),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(serial, parallel);
+        assert_eq!(serial, serial.t());
+        assert!((serial[[0, 5]] - 0.75).abs() < 1e-6);
+
+        // The last pool is kept and shared with clones, and replaced when
+        // the thread count changes
+        let pool = backend.pool(4).unwrap();
+        assert!(Arc::ptr_eq(&pool, &backend.clone().pool(4).unwrap()));
+        let other = backend.pool(2).unwrap();
+        assert_eq!(other.current_num_threads(), 2);
+        assert!(Arc::ptr_eq(&other, &backend.pool(2).unwrap()));
+        assert!(!Arc::ptr_eq(&pool, &backend.pool(4).unwrap()));
+    }
+
+    #[test]
+    fn test_label_values_as_features() {
+        // The unlabeled node stands in with its degree
+        let mut graph: GraphType = Graph::new_undirected();
+        let n1 = graph.add_node(Some(100));
+        let n2 = graph.add_node(Some(5));
+        let n3 = graph.add_node(None);
+        graph.add_edge(n1, n2, ());
+        graph.add_edge(n1, n3, ());
+        let graphs = intern_graphs(&[graph], &MissingLabelStrategy::Degree)
+            .unwrap()
+            .0;
+        assert_eq!(
+            labels_as_features(&graphs).unwrap()[0],
+            ndarray::array![[100.0], [5.0], [1.0]]
+        );
+
+        let mut graph = Graph::<Option<String>, (), _>::new_undirected();
+        graph.add_node(Some("C".to_string()));
+        let graphs = intern_graphs(&[graph], &MissingLabelStrategy::Error)
+            .unwrap()
+            .0;
+        let config = DistanceConfig {
+            enforce_continuous: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            NativeBackend::new().compute_distance(&graphs, None, &config),
+            Err(WwlError::NonNumericLabel { graph: 0, node: 0 })
+        );
+    }
+}
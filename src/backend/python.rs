@@ -0,0 +1,214 @@
+//! Backend delegating to the WWL Python library through an embedded interpreter
+
+use std::ffi::CString;
+
+use ndarray::Array2;
+use numpy::{PyArray2, PyArrayMethods};
+use pyo3::prelude::*;
+use pyo3::types::{PyAny, PyDict, PyList};
+use pythonize::pythonize;
+
+use super::environment::{Diagnostics, PythonEnvironment, ENVIRONMENT_SCRIPT};
+use super::reference::{prepare_graphs, PreparedGraph};
+use super::{labels_as_features, Backend};
+use crate::{DistanceConfig, KernelConfig, WlGraph, WwlError};
+
+/// Backend calling the `wwl` Python module
+///
+/// Requires the Python WWL library and its dependencies (`igraph`, `POT`,
+/// `numpy`, `scikit-learn`) to be importable by the embedded interpreter.
+///
+/// The backend can be shared between threads. Graphs are converted to plain
+/// edge and label lists before the interpreter lock is taken, so only the
+/// construction of the Python objects and the call into `wwl` itself run
+/// under it; calls from several threads still take turns on the interpreter.
+pub struct PythonBackend {
+    wwl: Py<PyAny>,
+}
+
+impl PythonBackend {
+    /// Imports the `wwl` module
+    pub fn new() -> Result<Self, WwlError> {
+        Self::with_environment(&PythonEnvironment::Default)
+    }
+
+    /// Imports the `wwl` module from the given environment
+    ///
+    /// The environment is checked with [`PythonBackend::diagnose`] first, so
+    /// that missing packages and known-incompatible versions are reported
+    /// here. [`PythonEnvironment::Interpreter`] is rejected, since the
+    /// embedded interpreter is fixed when the crate is built.
+    pub fn with_environment(environment: &PythonEnvironment) -> Result<Self, WwlError> {
+        Python::attach(|py| {
+            environment_report(py, environment)?.check()?;
+            let wwl = import(py, "wwl")?;
+            Ok(PythonBackend {
+                wwl: wwl.unbind().into(),
+            })
+        })
+    }
+
+    /// Reports the embedded interpreter and the packages it can import from
+    /// the given environment
+    pub fn diagnose(environment: &PythonEnvironment) -> Result<Diagnostics, WwlError> {
+        Python::attach(|py| environment_report(py, environment))
+    }
+}
+
+impl Backend for PythonBackend {
+    fn compute_kernel(
+        &self,
+        graphs: &[WlGraph],
+        node_features: Option<&[Array2<f64>]>,
+        config: &KernelConfig<()>,
+    ) -> Result<Array2<f64>, WwlError> {
+        let graphs = prepare_graphs(graphs)?;
+        Python::attach(|py| {
+            let wwl_module = self.wwl.bind(py);
+            let py_graphs = graphs_to_python(py, &graphs)?;
+
+            let kwargs = PyDict::new(py);
+
+            if let Some(features) = node_features {
+                kwargs.set_item("node_features", convert_features_to_python(py, features)?)?;
+            }
+            kwargs.set_item("num_iterations", config.num_iterations)?;
+            kwargs.set_item("sinkhorn", config.sinkhorn)?;
+            if let Some(g) = config.gamma {
+                kwargs.set_item("gamma", g)?;
+            }
+
+            // Call WWL with explicit node_features=None for categorical mode
+            if node_features.is_none() {
+                kwargs.set_item("node_features", py.None())?;
+            }
+
+            let result = wwl_module
+                .getattr("wwl")
+                .and_then(|f| f.call((&py_graphs,), Some(&kwargs)))?;
+
+            result
+                .downcast::<PyArray2<f64>>()
+                .map(|py_array| py_array.readonly().as_array().to_owned())
+                .map_err(|e| WwlError::Extraction(e.to_string()))
+        })
+    }
+
+    fn compute_distance(
+        &self,
+        graphs: &[WlGraph],
+        node_features: Option<&[Array2<f64>]>,
+        config: &DistanceConfig<()>,
+    ) -> Result<Array2<f64>, WwlError> {
+        let label_features = match node_features {
+            None if config.enforce_continuous => Some(labels_as_features(graphs)?),
+            _ => None,
+        };
+        let node_features = node_features.or(label_features.as_deref());
+        let graphs = prepare_graphs(graphs)?;
+        Python::attach(|py| {
+            let wwl_module = self.wwl.bind(py);
+            let py_graphs = graphs_to_python(py, &graphs)?;
+
+            let kwargs = PyDict::new(py);
+
+            if let Some(features) = node_features {
+                kwargs.set_item("node_features", convert_features_to_python(py, features)?)?;
+            }
+            kwargs.set_item("num_iterations", config.num_iterations)?;
+            kwargs.set_item("sinkhorn", config.sinkhorn)?;
+            kwargs.set_item("enforce_continuous", config.enforce_continuous)?;
+
+            let result = wwl_module
+                .getattr("pairwise_wasserstein_distance")
+                .and_then(|f| f.call((&py_graphs,), Some(&kwargs)))?;
+
+            result
+                .downcast::<PyArray2<f64>>()
+                .map(|py_array| py_array.readonly().as_array().to_owned())
+                .map_err(|e| WwlError::Extraction(e.to_string()))
+        })
+    }
+}
+
+/// Builds the list of undirected `igraph.Graph` objects expected by `wwl`
+fn graphs_to_python<'py>(
+    py: Python<'py>,
+    graphs: &[PreparedGraph],
+) -> Result<Bound<'py, PyList>, WwlError> {
+    let igraph = import(py, "igraph")?;
+    let py_graphs = graphs
+        .iter()
+        .map(|graph| {
+            let kwargs = PyDict::new(py);
+            kwargs.set_item("edges", pythonize(py, &graph.edges)?)?;
+            kwargs.set_item("n", graph.node_count)?;
+            kwargs.set_item("directed", false)?;
+
+            let py_graph = igraph.getattr("Graph")?.call((), Some(&kwargs))?;
+            py_graph
+                .getattr("vs")?
+                .set_item("label", pythonize(py, &graph.labels)?)?;
+            Ok(py_graph)
+        })
+        .collect::<PyResult<Vec<_>>>()?;
+    Ok(PyList::new(py, py_graphs)?)
+}
+
+/// Builds the list of per-graph feature arrays expected by `wwl`
+fn convert_features_to_python<'py>(
+    py: Python<'py>,
+    node_features: &[Array2<f64>],
+) -> PyResult<Bound<'py, PyList>> {
+    PyList::new(
+        py,
+        node_features
+            .iter()
+            .map(|features| PyArray2::from_array(py, features)),
+    )
+}
+
+/// Makes `environment` importable by the embedded interpreter and reports on
+/// it
+fn environment_report(
+    py: Python<'_>,
+    environment: &PythonEnvironment,
+) -> Result<Diagnostics, WwlError> {
+    let script = PyModule::from_code(
+        py,
+        &CString::new(ENVIRONMENT_SCRIPT).expect("script contains no NUL byte"),
+        c"environment.py",
+        c"wwl_environment",
+    )?;
+    match environment {
+        PythonEnvironment::Default => {}
+        PythonEnvironment::Virtualenv(root) => {
+            let found: bool = script
+                .getattr("add_virtualenv")?
+                .call1((root.to_string_lossy(),))?
+                .extract()?;
+            if !found {
+                return Err(WwlError::InvalidConfig(format!(
+                    "virtualenv {} has no site-packages for the embedded Python version",
+                    root.display()
+                )));
+            }
+        }
+        PythonEnvironment::Interpreter(path) => {
+            return Err(WwlError::InvalidConfig(format!(
+                "the embedded interpreter cannot be replaced by {}; use the worker backend",
+                path.display()
+            )));
+        }
+    }
+    let report: String = script.getattr("report")?.call0()?.extract()?;
+    Diagnostics::parse(&report)
+}
+
+/// Imports a module, reporting import failures as [`WwlError::ModuleNotFound`]
+fn import<'py>(py: Python<'py>, module: &str) -> Result<Bound<'py, PyModule>, WwlError> {
+    py.import(module).map_err(|e| WwlError::ModuleNotFound {
+        module: module.to_string(),
+        message: e.to_string(),
+    })
+}
@@ -0,0 +1,80 @@
+//! Graphs in the form expected by the reference Python implementation
+
+use crate::{InitialLabel, WlGraph, WwlError};
+
+/// Undirected graph as handed to `igraph`
+///
+/// Built without touching Python, so that conversion runs outside the
+/// interpreter lock or before a request is sent to a worker process.
+pub(crate) struct PreparedGraph {
+    pub node_count: usize,
+    /// Each undirected edge once, from its lower endpoint
+    pub edges: Vec<(usize, usize)>,
+    /// Initial node labels, see [`label_id`]
+    pub labels: Vec<i64>,
+}
+
+/// Converts graphs to [`PreparedGraph`]s, rejecting what the reference
+/// implementation cannot represent
+pub(crate) fn prepare_graphs(graphs: &[WlGraph]) -> Result<Vec<PreparedGraph>, WwlError> {
+    // The reference implementation has no notion of edge labels or
+    // weights, and dropping them would silently change the kernel
+    if graphs.iter().any(WlGraph::has_edge_labels) {
+        return Err(WwlError::Unsupported(
+            "edge labels require the native backend".to_string(),
+        ));
+    }
+    if graphs.iter().any(WlGraph::is_weighted) {
+        return Err(WwlError::Unsupported(
+            "edge weights require the native backend".to_string(),
+        ));
+    }
+    // igraph would merge in- and out-neighbours during refinement
+    if graphs.iter().any(WlGraph::is_directed) {
+        return Err(WwlError::Unsupported(
+            "directed graphs require the native backend".to_string(),
+        ));
+    }
+
+    Ok(graphs
+        .iter()
+        .map(|graph| {
+            let edges = (0..graph.node_count())
+                .flat_map(|a| {
+                    graph
+                        .neighbors(a)
+                        .iter()
+                        .filter(move |&&b| a <= b)
+                        .map(move |&b| (a, b))
+                })
+                .collect();
+
+            // Always set labels so every graph of the batch is embedded
+            // alike, with stand-ins for missing labels kept apart
+            let labels = graph
+                .labels()
+                .iter()
+                .map(|&label| label_id(label))
+                .collect();
+
+            PreparedGraph {
+                node_count: graph.node_count(),
+                edges,
+                labels,
+            }
+        })
+        .collect())
+}
+
+/// Categorical label handed to `igraph`
+///
+/// Interned ids are kept as is; stand-ins take negative values, which no
+/// interned id can take: `-1` for [`InitialLabel::Unknown`] and
+/// `-2 - degree` for [`InitialLabel::Degree`].
+fn label_id(label: InitialLabel) -> i64 {
+    match label {
+        InitialLabel::Label(id) => id as i64,
+        InitialLabel::Unknown => -1,
+        InitialLabel::Degree(degree) => -2 - degree as i64,
+    }
+}
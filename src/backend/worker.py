@@ -0,0 +1,123 @@
+"""Worker process of the `wwl` crate's WorkerBackend.
+
+Reads length-prefixed requests from stdin and answers each with one
+length-prefixed response on stdout. All integers and floats are little
+endian. See `src/backend/worker.rs` for the message layout.
+"""
+
+import struct
+import sys
+import traceback
+
+READY, MATRIX, EXCEPTION, IMPORT_ERROR = 0, 1, 2, 3
+KERNEL, DISTANCE = 0, 1
+
+
+def string(value):
+    data = value.encode("utf-8")
+    return struct.pack("<I", len(data)) + data
+
+
+class Reader:
+    def __init__(self, data, np):
+        self.data = data
+        self.offset = 0
+        self.np = np
+
+    def unpack(self, fmt):
+        values = struct.unpack_from(fmt, self.data, self.offset)
+        self.offset += struct.calcsize(fmt)
+        return values
+
+    def array(self, dtype, count):
+        array = self.np.frombuffer(self.data, dtype=dtype, count=count, offset=self.offset)
+        self.offset += array.nbytes
+        return array
+
+
+def compute(payload, np, igraph, wwl):
+    reader = Reader(payload, np)
+    op, num_iterations, sinkhorn, enforce_continuous, gamma = reader.unpack("<BIBBd")
+
+    (num_graphs,) = reader.unpack("<I")
+    graphs, sizes = [], []
+    for _ in range(num_graphs):
+        node_count, edge_count = reader.unpack("<II")
+        edges = reader.array("<u4", 2 * edge_count).reshape(edge_count, 2).tolist()
+        labels = reader.array("<i8", node_count).tolist()
+        graph = igraph.Graph(n=node_count, edges=edges, directed=False)
+        graph.vs["label"] = labels
+        graphs.append(graph)
+        sizes.append(node_count)
+
+    has_features, dims = reader.unpack("<BI")
+    features = None
+    if has_features:
+        features = [
+            reader.array("<f8", size * dims).reshape(size, dims).copy() for size in sizes
+        ]
+
+    if op == KERNEL:
+        kwargs = {}
+        if gamma == gamma:  # NaN stands for no gamma
+            kwargs["gamma"] = gamma
+        return wwl.wwl(
+            graphs,
+            node_features=features,
+            num_iterations=num_iterations,
+            sinkhorn=bool(sinkhorn),
+            **kwargs,
+        )
+    if op == DISTANCE:
+        return wwl.pairwise_wasserstein_distance(
+            graphs,
+            node_features=features,
+            num_iterations=num_iterations,
+            sinkhorn=bool(sinkhorn),
+            enforce_continuous=bool(enforce_continuous),
+        )
+    raise ValueError("unknown operation {}".format(op))
+
+
+def main():
+    requests = sys.stdin.buffer
+    responses = sys.stdout.buffer
+    # Anything printed by the libraries must not corrupt the protocol
+    sys.stdout = sys.stderr
+
+    def send(payload):
+        responses.write(struct.pack("<Q", len(payload)))
+        responses.write(payload)
+        responses.flush()
+
+    modules = {}
+    for name in ("numpy", "igraph", "wwl"):
+        try:
+            modules[name] = __import__(name)
+        except Exception as error:
+            send(bytes([IMPORT_ERROR]) + string(name) + string(str(error)))
+            return
+    np, igraph, wwl = modules["numpy"], modules["igraph"], modules["wwl"]
+    send(bytes([READY]))
+
+    while True:
+        header = requests.read(8)
+        if len(header) < 8:
+            return
+        (length,) = struct.unpack("<Q", header)
+        payload = requests.read(length)
+        try:
+            result = np.ascontiguousarray(compute(payload, np, igraph, wwl), dtype="<f8")
+            rows, cols = result.shape
+            send(bytes([MATRIX]) + struct.pack("<II", rows, cols) + result.tobytes())
+        except Exception as error:
+            send(
+                bytes([EXCEPTION])
+                + string(type(error).__name__)
+                + string(str(error))
+                + string(traceback.format_exc())
+            )
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,797 @@
+//! Backend running the WWL Python library in worker processes
+//!
+//! Each worker is a Python interpreter running `worker.py`, which talks to
+//! the backend over its stdin and stdout. Every message is a frame made of a
+//! `u64` byte length followed by the payload; all numbers are little endian.
+//!
+//! A request payload holds:
+//!
+//! - `u8` operation (`0` kernel, `1` distance), `u32` number of iterations,
+//!   `u8` sinkhorn flag, `u8` enforce-continuous flag, `f64` gamma (`NaN`
+//!   when unset)
+//! - `u32` number of graphs, then per graph `u32` node count, `u32` edge
+//!   count, the edges as `u32` pairs and one `i64` label per node
+//! - `u8` node-features flag and `u32` feature dimension, then per graph the
+//!   `node_count × dims` features as row-major `f64`
+//!
+//! A response payload starts with a `u8` tag: `0` ready (sent once after
+//! start-up), `1` a matrix as `u32` rows, `u32` columns and row-major `f64`
+//! entries, `2` a Python exception as type, message and traceback, `3` a
+//! failed import as module and message. Strings are `u32` lengths followed
+//! by UTF-8 bytes.
+
+use std::io::{self, BufReader, Read, Write};
+use std::path::Path;
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::Duration;
+
+use ndarray::Array2;
+
+use super::environment::{Diagnostics, PythonEnvironment, ENVIRONMENT_SCRIPT};
+use super::reference::{prepare_graphs, PreparedGraph};
+use super::{labels_as_features, Backend};
+use crate::{DistanceConfig, KernelConfig, WlGraph, WwlError};
+
+const WORKER_SCRIPT: &str = include_str!("worker.py");
+
+const READY: u8 = 0;
+const MATRIX: u8 = 1;
+const EXCEPTION: u8 = 2;
+const IMPORT_ERROR: u8 = 3;
+
+const KERNEL: u8 = 0;
+const DISTANCE: u8 = 1;
+
+/// Settings of the worker pool
+#[derive(Clone, Debug)]
+pub struct WorkerConfig {
+    /// Python environment whose interpreter is started for each worker
+    pub environment: PythonEnvironment,
+    /// Maximum number of worker processes, each serving one call at a time
+    pub pool_size: usize,
+    /// Longest time a call may wait for its worker, or `None` to wait
+    /// indefinitely; a worker that does not answer in time is stopped
+    pub timeout: Option<Duration>,
+}
+
+impl Default for WorkerConfig {
+    fn default() -> Self {
+        Self {
+            environment: PythonEnvironment::Default,
+            pool_size: thread::available_parallelism().map_or(1, |n| n.get()),
+            timeout: None,
+        }
+    }
+}
+
+impl WorkerConfig {
+    /// Checks the configuration before any worker is started
+    pub fn validate(&self) -> Result<(), WwlError> {
+        if self.pool_size == 0 {
+            return Err(WwlError::InvalidConfig(
+                "pool_size must be at least 1".to_string(),
+            ));
+        }
+        if self.timeout == Some(Duration::ZERO) {
+            return Err(WwlError::InvalidConfig(
+                "timeout must be positive".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Backend calling the `wwl` Python module in separate worker processes
+///
+/// Unlike [`PythonBackend`](super::PythonBackend), no interpreter is embedded
+/// in the host process, so a crashing interpreter cannot take it down and
+/// other users of pyo3 are unaffected. Workers are started on demand, up to
+/// `pool_size`, and calls from several threads run on different workers in
+/// parallel. A call reaching a worker that has died since its last call is
+/// retried once, on a newly started worker. A call on which a new worker
+/// dies fails, as the call itself most likely killed it. With a
+/// [`WorkerConfig::timeout`], a worker that does not answer in time is
+/// killed and the call fails with [`WwlError::WorkerTimeout`], without retry.
+pub struct WorkerBackend {
+    config: WorkerConfig,
+    pool: Mutex<Pool>,
+    released: Condvar,
+}
+
+/// Idle workers and the number of live ones, idle or busy
+struct Pool {
+    idle: Vec<Worker>,
+    live: usize,
+}
+
+impl WorkerBackend {
+    /// Starts a pool with the default configuration
+    pub fn new() -> Result<Self, WwlError> {
+        Self::with_config(WorkerConfig::default())
+    }
+
+    /// Starts a pool, checking the environment with
+    /// [`WorkerBackend::diagnose`] and starting a first worker
+    pub fn with_config(config: WorkerConfig) -> Result<Self, WwlError> {
+        config.validate()?;
+        Self::diagnose(&config.environment)?.check()?;
+        let worker = Worker::spawn(&config)?;
+        Ok(WorkerBackend {
+            config,
+            pool: Mutex::new(Pool {
+                idle: vec![worker],
+                live: 1,
+            }),
+            released: Condvar::new(),
+        })
+    }
+
+    /// Configuration of the pool
+    pub fn config(&self) -> &WorkerConfig {
+        &self.config
+    }
+
+    /// Reports the interpreter of the given environment and the packages it
+    /// can import, running it in a separate process
+    pub fn diagnose(environment: &PythonEnvironment) -> Result<Diagnostics, WwlError> {
+        let interpreter = environment.interpreter();
+        let output = Command::new(&interpreter)
+            .arg("-c")
+            .arg(ENVIRONMENT_SCRIPT)
+            .stdin(Stdio::null())
+            .output()
+            .map_err(|err| cannot_start(&interpreter, err))?;
+        if !output.status.success() {
+            return Err(WwlError::Worker(format!(
+                "`{}` failed: {}",
+                interpreter.display(),
+                String::from_utf8_lossy(&output.stderr).trim_end()
+            )));
+        }
+        Diagnostics::parse(&String::from_utf8_lossy(&output.stdout))
+    }
+
+    /// Sends a request to a worker, replacing a worker that died
+    fn call(&self, request: &[u8]) -> Result<Array2<f64>, WwlError> {
+        let mut worker = self.checkout()?;
+        loop {
+            let err = match worker.call(request, self.config.timeout) {
+                Ok(response) => {
+                    self.checkin(worker);
+                    return response;
+                }
+                Err(err) => err,
+            };
+            self.discard();
+            if err.kind() == io::ErrorKind::TimedOut {
+                return Err(WwlError::WorkerTimeout(
+                    self.config
+                        .timeout
+                        .expect("only calls with a timeout time out"),
+                ));
+            }
+            if worker.served == 0 {
+                return Err(WwlError::Worker(format!("worker process failed: {}", err)));
+            }
+            worker = self.start()?;
+        }
+    }
+
+    /// Takes an idle worker, starting one if the pool is not full
+    fn checkout(&self) -> Result<Worker, WwlError> {
+        let mut pool = self.lock();
+        loop {
+            if let Some(worker) = pool.idle.pop() {
+                return Ok(worker);
+            }
+            if pool.live < self.config.pool_size {
+                pool.live += 1;
+                drop(pool);
+                return Worker::spawn(&self.config).inspect_err(|_| self.discard());
+            }
+            pool = self
+                .released
+                .wait(pool)
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+        }
+    }
+
+    /// Starts a new worker, retiring an idle one if the pool is full
+    fn start(&self) -> Result<Worker, WwlError> {
+        let mut retired = Vec::new();
+        let mut pool = self.lock();
+        while pool.live >= self.config.pool_size {
+            match pool.idle.pop() {
+                Some(worker) => {
+                    retired.push(worker);
+                    pool.live -= 1;
+                }
+                None => {
+                    pool = self
+                        .released
+                        .wait(pool)
+                        .unwrap_or_else(|poisoned| poisoned.into_inner())
+                }
+            }
+        }
+        pool.live += 1;
+        drop(pool);
+        drop(retired);
+        Worker::spawn(&self.config).inspect_err(|_| self.discard())
+    }
+
+    fn checkin(&self, worker: Worker) {
+        self.lock().idle.push(worker);
+        self.released.notify_one();
+    }
+
+    /// Forgets a worker that died or could not be started
+    fn discard(&self) {
+        self.lock().live -= 1;
+        self.released.notify_one();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Pool> {
+        self.pool
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Backend for WorkerBackend {
+    fn compute_kernel(
+        &self,
+        graphs: &[WlGraph],
+        node_features: Option<&[Array2<f64>]>,
+        config: &KernelConfig<()>,
+    ) -> Result<Array2<f64>, WwlError> {
+        let request = encode_request(
+            KERNEL,
+            config.num_iterations,
+            config.sinkhorn,
+            false,
+            config.gamma,
+            &prepare_graphs(graphs)?,
+            node_features,
+        )?;
+        self.call(&request)
+    }
+
+    fn compute_distance(
+        &self,
+        graphs: &[WlGraph],
+        node_features: Option<&[Array2<f64>]>,
+        config: &DistanceConfig<()>,
+    ) -> Result<Array2<f64>, WwlError> {
+        let label_features = match node_features {
+            None if config.enforce_continuous => Some(labels_as_features(graphs)?),
+            _ => None,
+        };
+        let request = encode_request(
+            DISTANCE,
+            config.num_iterations,
+            config.sinkhorn,
+            config.enforce_continuous,
+            None,
+            &prepare_graphs(graphs)?,
+            node_features.or(label_features.as_deref()),
+        )?;
+        self.call(&request)
+    }
+}
+
+/// Worker process with threads writing its requests and reading its
+/// responses, so that waiting for it can time out
+struct Worker {
+    child: Child,
+    requests: Sender<Vec<u8>>,
+    responses: Receiver<io::Result<Vec<u8>>>,
+    /// Number of requests answered so far
+    served: usize,
+}
+
+impl Worker {
+    /// Starts a worker and waits until it has imported `wwl`
+    fn spawn(config: &WorkerConfig) -> Result<Self, WwlError> {
+        let interpreter = config.environment.interpreter();
+        let mut child = Command::new(&interpreter)
+            .arg("-c")
+            .arg(WORKER_SCRIPT)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|err| cannot_start(&interpreter, err))?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = BufReader::new(child.stdout.take().expect("stdout is piped"));
+        let (requests, responses) = pipe_threads(stdin, stdout);
+        let worker = Worker {
+            child,
+            requests,
+            responses,
+            served: 0,
+        };
+
+        let ready = worker
+            .receive(None)
+            .and_then(|frame| decode_response(&frame));
+        match ready {
+            Ok(Response::Ready) => Ok(worker),
+            Ok(Response::Failed(err)) => Err(err),
+            Ok(Response::Matrix(_)) => Err(WwlError::Worker(
+                "worker sent a matrix before being ready".to_string(),
+            )),
+            Err(err) => Err(WwlError::Worker(format!(
+                "worker exited during start-up: {}",
+                err
+            ))),
+        }
+    }
+
+    /// Sends one request; I/O errors, including [`io::ErrorKind::TimedOut`],
+    /// mean the worker can no longer be used
+    fn call(
+        &mut self,
+        request: &[u8],
+        timeout: Option<Duration>,
+    ) -> io::Result<Result<Array2<f64>, WwlError>> {
+        self.requests
+            .send(request.to_vec())
+            .map_err(|_| closed_pipe())?;
+        let response = decode_response(&self.receive(timeout)?)?;
+        self.served += 1;
+        match response {
+            Response::Matrix(matrix) => Ok(Ok(matrix)),
+            Response::Failed(err) => Ok(Err(err)),
+            Response::Ready => Err(invalid_data("unexpected ready message")),
+        }
+    }
+
+    /// Waits for the next frame from the worker
+    fn receive(&self, timeout: Option<Duration>) -> io::Result<Vec<u8>> {
+        match timeout {
+            None => self.responses.recv().map_err(|_| closed_pipe())?,
+            Some(timeout) => match self.responses.recv_timeout(timeout) {
+                Ok(frame) => frame,
+                Err(RecvTimeoutError::Timeout) => Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "worker did not answer in time",
+                )),
+                Err(RecvTimeoutError::Disconnected) => Err(closed_pipe()),
+            },
+        }
+    }
+}
+
+/// Starts the threads writing request frames to a worker and reading its
+/// response frames; both end once the worker exits or is dropped
+fn pipe_threads(
+    mut stdin: ChildStdin,
+    mut stdout: BufReader<ChildStdout>,
+) -> (Sender<Vec<u8>>, Receiver<io::Result<Vec<u8>>>) {
+    let (requests, pending) = mpsc::channel::<Vec<u8>>();
+    let (answered, responses) = mpsc::channel();
+    let failed = answered.clone();
+    thread::spawn(move || {
+        for request in pending {
+            if let Err(err) = write_frame(&mut stdin, &request) {
+                let _ = failed.send(Err(err));
+                return;
+            }
+        }
+    });
+    thread::spawn(move || loop {
+        let frame = read_frame(&mut stdout);
+        let done = frame.is_err();
+        if answered.send(frame).is_err() || done {
+            return;
+        }
+    });
+    (requests, responses)
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+enum Response {
+    Ready,
+    Matrix(Array2<f64>),
+    Failed(WwlError),
+}
+
+fn encode_request(
+    operation: u8,
+    num_iterations: usize,
+    sinkhorn: bool,
+    enforce_continuous: bool,
+    gamma: Option<f64>,
+    graphs: &[PreparedGraph],
+    node_features: Option<&[Array2<f64>]>,
+) -> Result<Vec<u8>, WwlError> {
+    let mut request = vec![operation];
+    put_u32(&mut request, num_iterations)?;
+    request.push(sinkhorn as u8);
+    request.push(enforce_continuous as u8);
+    request.extend_from_slice(&gamma.unwrap_or(f64::NAN).to_le_bytes());
+
+    put_u32(&mut request, graphs.len())?;
+    for graph in graphs {
+        put_u32(&mut request, graph.node_count)?;
+        put_u32(&mut request, graph.edges.len())?;
+        for &(a, b) in &graph.edges {
+            put_u32(&mut request, a)?;
+            put_u32(&mut request, b)?;
+        }
+        for label in &graph.labels {
+            request.extend_from_slice(&label.to_le_bytes());
+        }
+    }
+
+    request.push(node_features.is_some() as u8);
+    let dims = node_features
+        .and_then(|features| features.first())
+        .map_or(0, |features| features.ncols());
+    put_u32(&mut request, dims)?;
+    for features in node_features.unwrap_or_default() {
+        for value in features {
+            request.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+    Ok(request)
+}
+
+fn put_u32(buffer: &mut Vec<u8>, value: usize) -> Result<(), WwlError> {
+    let value = u32::try_from(value).map_err(|_| {
+        WwlError::Unsupported(format!("{} exceeds the worker protocol limits", value))
+    })?;
+    buffer.extend_from_slice(&value.to_le_bytes());
+    Ok(())
+}
+
+fn decode_response(payload: &[u8]) -> io::Result<Response> {
+    let mut decoder = Decoder(payload);
+    let response = match decoder.u8()? {
+        READY => Response::Ready,
+        MATRIX => {
+            let rows = decoder.u32()? as usize;
+            let cols = decoder.u32()? as usize;
+            let values = (0..rows * cols)
+                .map(|_| decoder.f64())
+                .collect::<io::Result<Vec<_>>>()?;
+            let matrix = Array2::from_shape_vec((rows, cols), values)
+                .map_err(|err| invalid_data(&err.to_string()))?;
+            Response::Matrix(matrix)
+        }
+        EXCEPTION => {
+            let exception_type = decoder.string()?;
+            let message = decoder.string()?;
+            let traceback = decoder.string()?;
+            Response::Failed(WwlError::Python {
+                exception_type,
+                message,
+                traceback: Some(traceback).filter(|traceback| !traceback.is_empty()),
+            })
+        }
+        IMPORT_ERROR => Response::Failed(WwlError::ModuleNotFound {
+            module: decoder.string()?,
+            message: decoder.string()?,
+        }),
+        tag => return Err(invalid_data(&format!("unknown response tag {}", tag))),
+    };
+    if !decoder.0.is_empty() {
+        return Err(invalid_data("trailing bytes in response"));
+    }
+    Ok(response)
+}
+
+/// Reads little-endian values from the front of a payload
+struct Decoder<'a>(&'a [u8]);
+
+impl Decoder<'_> {
+    fn take<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        if self.0.len() < N {
+            return Err(invalid_data("truncated response"));
+        }
+        let (head, rest) = self.0.split_at(N);
+        self.0 = rest;
+        Ok(head.try_into().expect("length checked"))
+    }
+
+    fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.take::<1>()?[0])
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        Ok(u32::from_le_bytes(self.take()?))
+    }
+
+    fn f64(&mut self) -> io::Result<f64> {
+        Ok(f64::from_le_bytes(self.take()?))
+    }
+
+    fn string(&mut self) -> io::Result<String> {
+        let len = self.u32()? as usize;
+        if self.0.len() < len {
+            return Err(invalid_data("truncated response"));
+        }
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        String::from_utf8(bytes.to_vec()).map_err(|err| invalid_data(&err.to_string()))
+    }
+}
+
+fn write_frame(writer: &mut impl Write, payload: &[u8]) -> io::Result<()> {
+    writer.write_all(&(payload.len() as u64).to_le_bytes())?;
+    writer.write_all(payload)?;
+    writer.flush()
+}
+
+fn read_frame(reader: &mut impl Read) -> io::Result<Vec<u8>> {
+    fn closed(err: io::Error) -> io::Error {
+        if err.kind() == io::ErrorKind::UnexpectedEof {
+            io::Error::new(err.kind(), "worker closed its output")
+        } else {
+            err
+        }
+    }
+
+    let mut length = [0; 8];
+    reader.read_exact(&mut length).map_err(closed)?;
+    let mut payload = vec![0; u64::from_le_bytes(length) as usize];
+    reader.read_exact(&mut payload).map_err(closed)?;
+    Ok(payload)
+}
+
+fn cannot_start(interpreter: &Path, err: io::Error) -> WwlError {
+    WwlError::Worker(format!("cannot start `{}`: {}", interpreter.display(), err))
+}
+
+fn closed_pipe() -> io::Error {
+    io::Error::new(io::ErrorKind::BrokenPipe, "worker closed its pipes")
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(value: &str) -> Vec<u8> {
+        let mut bytes = (value.len() as u32).to_le_bytes().to_vec();
+        bytes.extend_from_slice(value.as_bytes());
+        bytes
+    }
+
+    #[test]
+    fn test_decode_responses() {
+        let mut payload = vec![MATRIX];
+        payload.extend_from_slice(&1u32.to_le_bytes());
+        payload.extend_from_slice(&2u32.to_le_bytes());
+        payload.extend_from_slice(&0.0f64.to_le_bytes());
+        payload.extend_from_slice(&0.75f64.to_le_bytes());
+        let mut frame = Vec::new();
+        write_frame(&mut frame, &payload).unwrap();
+
+        let payload = read_frame(&mut frame.as_slice()).unwrap();
+        assert!(matches!(
+            decode_response(&payload).unwrap(),
+            Response::Matrix(matrix) if matrix == ndarray::array![[0.0, 0.75]]
+        ));
+
+        let mut payload = vec![EXCEPTION];
+        payload.extend(string("ValueError"));
+        payload.extend(string("bad input"));
+        payload.extend(string(""));
+        assert!(matches!(
+            decode_response(&payload).unwrap(),
+            Response::Failed(WwlError::Python { exception_type, traceback: None, .. })
+                if exception_type == "ValueError"
+        ));
+
+        // Truncated matrices and unknown tags break the protocol
+        assert!(decode_response(&[MATRIX, 1, 0, 0, 0, 1, 0, 0, 0]).is_err());
+        assert!(decode_response(&[42]).is_err());
+    }
+
+    #[test]
+    fn test_encode_request() {
+        let graphs = [PreparedGraph {
+            node_count: 2,
+            edges: vec![(0, 1)],
+            labels: vec![1, -1],
+        }];
+        let features = [ndarray::array![[1.0], [2.0]]];
+        let request =
+            encode_request(KERNEL, 3, true, false, None, &graphs, Some(&features)).unwrap();
+
+        // Header, graph count, one graph, features flag and dims, features
+        assert_eq!(request.len(), 15 + 4 + (8 + 8 + 16) + 5 + 16);
+        assert_eq!(request[..7], [KERNEL, 3, 0, 0, 0, 1, 0]);
+        assert!(f64::from_le_bytes(request[7..15].try_into().unwrap()).is_nan());
+    }
+
+    #[test]
+    fn test_matches_python_reference() {
+        use crate::{intern_graphs, GraphType, MissingLabelStrategy};
+        use petgraph::Graph;
+
+        let backend = match WorkerBackend::new() {
+            Ok(backend) => backend,
+            Err(_) => {
+                println!("Skipping worker test - WWL module not available");
+                return;
+            }
+        };
+        let mut graph1: GraphType = Graph::new_undirected();
+        let n1 = graph1.add_node(Some(1));
+        let n2 = graph1.add_node(Some(2));
+        graph1.add_edge(n1, n2, ());
+        let mut graph2 = graph1.clone();
+        let n3 = graph2.add_node(Some(3));
+        graph2.add_edge(n2, n3, ());
+        let graphs = intern_graphs(&[graph1, graph2], &MissingLabelStrategy::Error)
+            .unwrap()
+            .0;
+
+        let distances = backend
+            .compute_distance(&graphs, None, &DistanceConfig::default())
+            .unwrap();
+        assert!((distances[[0, 1]] - 0.75).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_missing_interpreter() {
+        let config = WorkerConfig {
+            environment: PythonEnvironment::Interpreter("/nonexistent/python".into()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            WorkerBackend::with_config(config),
+            Err(WwlError::Worker(_))
+        ));
+
+        let config = WorkerConfig {
+            pool_size: 0,
+            ..Default::default()
+        };
+        assert!(matches!(
+            WorkerBackend::with_config(config),
+            Err(WwlError::InvalidConfig(_))
+        ));
+    }
+    /// Fake interpreter answering every request with `[[0.5]]` after a short
+    /// delay, recording the pid of each worker it starts in `pids`; requests
+    /// go unanswered while a `silent` file exists next to it
+    #[cfg(target_os = "linux")]
+    const FAKE_INTERPRETER: &str = r#"#!/bin/sh
+case "$2" in
+*add_virtualenv*)
+    printf 'python\t3.11.7\t/fake\nwwl\tfound\t0.1\nigraph\tfound\t0.11.8\not\tfound\t0.9.4\nnumpy\tfound\t1.26.4\nsklearn\tfound\t1.5.0'
+    exit 0;;
+esac
+echo $$ >> "$(dirname "$0")/pids"
+printf '\001\000\000\000\000\000\000\000\000'
+while :; do
+    length=$(dd bs=8 count=1 iflag=fullblock 2>/dev/null | od -An -tu8 | tr -d ' ')
+    [ -n "$length" ] || exit 0
+    dd bs="$length" count=1 iflag=fullblock > /dev/null 2>&1
+    [ -e "$(dirname "$0")/silent" ] && continue
+    sleep 0.2
+    printf '\021\000\000\000\000\000\000\000\001\001\000\000\000\001\000\000\000'
+    printf '\000\000\000\000\000\000\340\077'
+done
+"#;
+
+    /// Writes [`FAKE_INTERPRETER`] to a fresh directory named after `test`
+    #[cfg(target_os = "linux")]
+    fn fake_interpreter(test: &str) -> std::path::PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+
+        let directory =
+            std::env::temp_dir().join(format!("wwl-worker-{}-{}", test, std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let interpreter = directory.join("python");
+        std::fs::write(&interpreter, FAKE_INTERPRETER).unwrap();
+        std::fs::set_permissions(&interpreter, std::fs::Permissions::from_mode(0o755)).unwrap();
+        directory
+    }
+
+    /// Pids of the workers started from `directory`
+    #[cfg(target_os = "linux")]
+    fn pids(directory: &Path) -> Vec<String> {
+        std::fs::read_to_string(directory.join("pids"))
+            .unwrap()
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_dead_worker_is_replaced_once() {
+        use std::sync::Arc;
+
+        let directory = fake_interpreter("restart");
+        let pids = || pids(&directory);
+        let backend = Arc::new(
+            WorkerBackend::with_config(WorkerConfig {
+                environment: PythonEnvironment::Interpreter(directory.join("python")),
+                pool_size: 2,
+                timeout: None,
+            })
+            .unwrap(),
+        );
+        let request = encode_request(DISTANCE, 1, false, false, None, &[], None).unwrap();
+
+        // Two overlapping calls leave two idle workers, both used once
+        let calls: Vec<_> = (0..2)
+            .map(|_| {
+                let backend = backend.clone();
+                let request = request.clone();
+                thread::spawn(move || backend.call(&request))
+            })
+            .collect();
+        for call in calls {
+            assert_eq!(call.join().unwrap().unwrap(), ndarray::array![[0.5]]);
+        }
+        assert_eq!(pids().len(), 2);
+
+        // Kill both: the call reaches one of them and moves to a new worker
+        // instead of trying the other dead one
+        for pid in pids() {
+            Command::new("kill").args(["-9", &pid]).status().unwrap();
+        }
+        thread::sleep(std::time::Duration::from_millis(100));
+        assert_eq!(backend.call(&request).unwrap(), ndarray::array![[0.5]]);
+        assert_eq!(pids().len(), 3);
+
+        drop(backend);
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_silent_worker_times_out() {
+        use std::time::Instant;
+
+        let directory = fake_interpreter("timeout");
+        let timeout = Duration::from_millis(500);
+        let backend = WorkerBackend::with_config(WorkerConfig {
+            environment: PythonEnvironment::Interpreter(directory.join("python")),
+            pool_size: 1,
+            timeout: Some(timeout),
+        })
+        .unwrap();
+        let request = encode_request(DISTANCE, 1, false, false, None, &[], None).unwrap();
+        assert_eq!(backend.call(&request).unwrap(), ndarray::array![[0.5]]);
+
+        // The call gives up once the timeout has passed and kills the worker
+        std::fs::write(directory.join("silent"), b"").unwrap();
+        let started = Instant::now();
+        assert_eq!(
+            backend.call(&request),
+            Err(WwlError::WorkerTimeout(timeout))
+        );
+        assert!(started.elapsed() >= timeout);
+        assert!(started.elapsed() < 10 * timeout);
+        let silent = pids(&directory);
+        assert_eq!(silent.len(), 1);
+        assert!(!Path::new(&format!("/proc/{}", silent[0])).exists());
+
+        // The next call runs on a new worker
+        std::fs::remove_file(directory.join("silent")).unwrap();
+        assert_eq!(backend.call(&request).unwrap(), ndarray::array![[0.5]]);
+        assert_eq!(pids(&directory).len(), 2);
+
+        drop(backend);
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,674 @@
+//! Cache of per-graph WL embeddings
+//!
+//! [`EmbeddingCache`] lets [`NativeBackend`](crate::NativeBackend) skip
+//! propagation for graphs it has embedded before, in this run or, with a
+//! cache directory, in an earlier one. Entries are keyed by a hash of a
+//! canonical little-endian encoding of everything the embedding depends on:
+//! structure, labels, edge weights, node features and the number of
+//! iterations. Labels enter the key through hashes of the labels themselves
+//! rather than their interned ids, which depend on the collection a graph
+//! was interned with.
+//!
+//! Categorical embeddings are cached per graph, so their label ids cannot be
+//! compressed across the collection as [`categorical_propagation`] does.
+//! Cached embeddings instead use hashes of the rooted subtrees as ids, which
+//! yields the same distances.
+//!
+//! [`categorical_propagation`]: crate::propagation::categorical_propagation
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::fs;
+use std::hash::Hasher;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use ndarray::{Array2, ArrayView1};
+
+use crate::graph::{StableHasher, WlGraph};
+use crate::propagation::continuous_propagation;
+use crate::WwlError;
+
+/// Version of the key derivation and of the entry file layout
+const FORMAT_VERSION: u8 = 2;
+
+/// Magic bytes opening every entry file
+const MAGIC: &[u8; 4] = b"WWLE";
+
+const CATEGORICAL: u8 = 0;
+const CONTINUOUS: u8 = 1;
+
+/// Number of lookups answered from the cache and computed anew
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: usize,
+    pub misses: usize,
+}
+
+/// In-memory and optional on-disk store of per-graph embeddings
+///
+/// Clones share the same store, so one cache can serve several backends and
+/// threads. Memory holds every entry unless bounded with
+/// [`EmbeddingCache::set_capacity`], which evicts the oldest entries first;
+/// [`EmbeddingCache::clear`] drops them all. On disk, every entry is one file
+/// named after its key, written atomically, and unreadable files are
+/// recomputed and overwritten. Files are never removed by the cache.
+#[derive(Clone, Default)]
+pub struct EmbeddingCache {
+    inner: Arc<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    memory: Mutex<Memory>,
+    directory: Option<PathBuf>,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+}
+
+/// Entries held in memory, in insertion order for eviction
+#[derive(Default)]
+struct Memory {
+    entries: HashMap<u64, Embedding>,
+    order: VecDeque<u64>,
+    capacity: Option<usize>,
+}
+
+impl Memory {
+    fn insert(&mut self, key: u64, embedding: Embedding) {
+        if self.entries.insert(key, embedding).is_none() {
+            self.order.push_back(key);
+        }
+        self.evict();
+    }
+
+    fn evict(&mut self) {
+        let capacity = self.capacity.unwrap_or(usize::MAX);
+        while self.entries.len() > capacity {
+            let oldest = self.order.pop_front().expect("one key per entry");
+            self.entries.remove(&oldest);
+        }
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+    }
+}
+
+#[derive(Clone)]
+enum Embedding {
+    Categorical(Array2<u64>),
+    Continuous(Array2<f64>),
+}
+
+impl EmbeddingCache {
+    /// Creates a cache kept in memory only
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a cache backed by `directory`, which is created if needed
+    ///
+    /// Entries written by earlier runs are picked up on demand.
+    pub fn with_directory(directory: impl Into<PathBuf>) -> Result<Self, WwlError> {
+        let directory = directory.into();
+        fs::create_dir_all(&directory).map_err(|err| cache_error(&directory, err))?;
+        Ok(EmbeddingCache {
+            inner: Arc::new(Inner {
+                directory: Some(directory),
+                ..Default::default()
+            }),
+        })
+    }
+
+    /// Directory backing the cache, if any
+    pub fn directory(&self) -> Option<&Path> {
+        self.inner.directory.as_deref()
+    }
+
+    /// Bounds the entries held in memory to `entries`, evicting the oldest
+    /// ones as needed; entries on disk are kept
+    pub fn set_capacity(&self, entries: usize) {
+        let mut memory = self.memory();
+        memory.capacity = Some(entries);
+        memory.evict();
+    }
+
+    /// Bound on the entries held in memory, if any
+    pub fn capacity(&self) -> Option<usize> {
+        self.memory().capacity
+    }
+
+    /// Number of entries held in memory
+    pub fn len(&self) -> usize {
+        self.memory().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drops the entries held in memory, keeping those on disk
+    pub fn clear(&self) {
+        self.memory().clear();
+    }
+
+    /// Lookups answered from the cache and computed anew so far
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.inner.hits.load(Ordering::Relaxed),
+            misses: self.inner.misses.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Categorical embeddings of `graphs` with hashed label ids
+    pub(crate) fn categorical(
+        &self,
+        graphs: &[WlGraph],
+        num_iterations: usize,
+    ) -> Result<Vec<Array2<u64>>, WwlError> {
+        graphs
+            .iter()
+            .map(|graph| {
+                let labels = ContentLabels::of(graph)?;
+                let key = key(CATEGORICAL, graph, &labels, None, num_iterations);
+                let embedding = self.get_or_insert(key, CATEGORICAL, || {
+                    Embedding::Categorical(hashed_propagation(graph, &labels, num_iterations))
+                })?;
+                match embedding {
+                    Embedding::Categorical(embedding) => Ok(embedding),
+                    Embedding::Continuous(_) => unreachable!("kind is part of the key"),
+                }
+            })
+            .collect()
+    }
+
+    /// Continuous embeddings of `graphs` with their node features
+    pub(crate) fn continuous(
+        &self,
+        graphs: &[WlGraph],
+        node_features: &[Array2<f64>],
+        num_iterations: usize,
+    ) -> Result<Vec<Array2<f64>>, WwlError> {
+        graphs
+            .iter()
+            .zip(node_features)
+            .map(|(graph, features)| {
+                let labels = ContentLabels::of(graph)?;
+                let key = key(CONTINUOUS, graph, &labels, Some(features), num_iterations);
+                let embedding = self.get_or_insert(key, CONTINUOUS, || {
+                    let graphs = std::slice::from_ref(graph);
+                    let features = std::slice::from_ref(features);
+                    let embedding = continuous_propagation(graphs, features, num_iterations);
+                    Embedding::Continuous(embedding.into_iter().next().expect("one graph"))
+                })?;
+                match embedding {
+                    Embedding::Continuous(embedding) => Ok(embedding),
+                    Embedding::Categorical(_) => unreachable!("kind is part of the key"),
+                }
+            })
+            .collect()
+    }
+
+    /// Looks `key` up in memory, then on disk, computing it if absent
+    fn get_or_insert(
+        &self,
+        key: u64,
+        kind: u8,
+        compute: impl FnOnce() -> Embedding,
+    ) -> Result<Embedding, WwlError> {
+        if let Some(embedding) = self.memory().entries.get(&key) {
+            self.inner.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(embedding.clone());
+        }
+
+        let path = self.path(key);
+        let stored = path
+            .as_deref()
+            .and_then(|path| fs::read(path).ok())
+            .and_then(|bytes| decode(&bytes, kind));
+        let embedding = match stored {
+            Some(embedding) => {
+                self.inner.hits.fetch_add(1, Ordering::Relaxed);
+                embedding
+            }
+            None => {
+                self.inner.misses.fetch_add(1, Ordering::Relaxed);
+                let embedding = compute();
+                if let Some(path) = &path {
+                    write_atomically(path, &encode(&embedding))
+                        .map_err(|err| cache_error(path, err))?;
+                }
+                embedding
+            }
+        };
+        self.memory().insert(key, embedding.clone());
+        Ok(embedding)
+    }
+
+    fn path(&self, key: u64) -> Option<PathBuf> {
+        let directory = self.inner.directory.as_ref()?;
+        Some(directory.join(format!("{:016x}.wwle", key)))
+    }
+
+    fn memory(&self) -> std::sync::MutexGuard<'_, Memory> {
+        self.inner
+            .memory
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl fmt::Debug for EmbeddingCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EmbeddingCache")
+            .field("directory", &self.inner.directory)
+            .field("len", &self.len())
+            .field("stats", &self.stats())
+            .finish()
+    }
+}
+
+/// Labels of a graph as hashes of the labels themselves, aligned with its
+/// adjacency lists
+struct ContentLabels {
+    nodes: Vec<u64>,
+    outgoing: Vec<Vec<Option<u64>>>,
+    incoming: Vec<Vec<Option<u64>>>,
+}
+
+impl ContentLabels {
+    fn of(graph: &WlGraph) -> Result<Self, WwlError> {
+        let edges = |labels: &[Option<usize>]| -> Result<Vec<Option<u64>>, WwlError> {
+            labels
+                .iter()
+                .map(|&label| graph.edge_label_hash(label))
+                .collect()
+        };
+        let nodes = 0..graph.node_count();
+        Ok(ContentLabels {
+            nodes: nodes
+                .clone()
+                .map(|node| graph.node_label_hash(node))
+                .collect::<Result<_, _>>()?,
+            outgoing: nodes
+                .clone()
+                .map(|node| edges(graph.edge_labels(node)))
+                .collect::<Result<_, _>>()?,
+            incoming: nodes
+                .map(|node| edges(graph.in_edge_labels(node)))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+/// Key of the embedding of one graph
+///
+/// Every field is written as fixed-width little-endian bytes, prefixed by
+/// its length where it varies, so keys do not depend on the platform or on
+/// how the standard library hashes its types.
+fn key(
+    kind: u8,
+    graph: &WlGraph,
+    labels: &ContentLabels,
+    features: Option<&Array2<f64>>,
+    num_iterations: usize,
+) -> u64 {
+    let mut hasher = StableHasher::default();
+    hasher.write_u8(FORMAT_VERSION);
+    hasher.write_u8(kind);
+    hasher.write_u64(num_iterations as u64);
+    hasher.write_u8(graph.is_directed() as u8);
+    hasher.write_u64(graph.node_count() as u64);
+    for node in 0..graph.node_count() {
+        hasher.write_u64(labels.nodes[node]);
+        let neighbors = graph.neighbors(node);
+        hasher.write_u64(neighbors.len() as u64);
+        for ((&neighbor, label), weight) in neighbors
+            .iter()
+            .zip(&labels.outgoing[node])
+            .zip(graph.edge_weights(node))
+        {
+            hasher.write_u64(neighbor as u64);
+            write_edge_label(&mut hasher, *label);
+            hasher.write_u64(weight.to_bits());
+        }
+    }
+    if let Some(features) = features {
+        hasher.write_u8(1);
+        hasher.write_u64(features.nrows() as u64);
+        hasher.write_u64(features.ncols() as u64);
+        for value in features {
+            hasher.write_u64(value.to_bits());
+        }
+    } else {
+        hasher.write_u8(0);
+    }
+    hasher.finish()
+}
+
+fn write_edge_label(hasher: &mut StableHasher, label: Option<u64>) {
+    match label {
+        Some(label) => {
+            hasher.write_u8(1);
+            hasher.write_u64(label);
+        }
+        None => hasher.write_u8(0),
+    }
+}
+
+/// Categorical propagation of a single graph, with hashes of the initial
+/// labels and relabeling keys as label ids
+///
+/// Two nodes get equal ids exactly when [`categorical_propagation`] would
+/// give them equal ids, barring hash collisions, whatever the collection.
+///
+/// [`categorical_propagation`]: crate::propagation::categorical_propagation
+fn hashed_propagation(
+    graph: &WlGraph,
+    labels: &ContentLabels,
+    num_iterations: usize,
+) -> Array2<u64> {
+    let mut current = labels.nodes.clone();
+    let mut sequence = Array2::zeros((graph.node_count(), num_iterations + 1));
+    sequence.column_mut(0).assign(&ArrayView1::from(&current));
+
+    for iteration in 1..=num_iterations {
+        current = (0..graph.node_count())
+            .map(|node| {
+                let mut hasher = StableHasher::default();
+                hasher.write_u64(current[node]);
+                write_signature(
+                    &mut hasher,
+                    graph.neighbors(node),
+                    &labels.outgoing[node],
+                    &current,
+                );
+                write_signature(
+                    &mut hasher,
+                    graph.in_neighbors(node),
+                    &labels.incoming[node],
+                    &current,
+                );
+                hasher.finish()
+            })
+            .collect();
+        sequence
+            .column_mut(iteration)
+            .assign(&ArrayView1::from(&current));
+    }
+    sequence
+}
+
+/// Writes the sorted multiset of (edge label, neighbour label) pairs of a
+/// node, prefixed by its size
+fn write_signature(
+    hasher: &mut StableHasher,
+    neighbors: &[usize],
+    edge_labels: &[Option<u64>],
+    current: &[u64],
+) {
+    let mut signature: Vec<(Option<u64>, u64)> = edge_labels
+        .iter()
+        .zip(neighbors)
+        .map(|(&edge, &neighbor)| (edge, current[neighbor]))
+        .collect();
+    signature.sort_unstable();
+    hasher.write_u64(signature.len() as u64);
+    for (edge, label) in signature {
+        write_edge_label(hasher, edge);
+        hasher.write_u64(label);
+    }
+}
+
+/// Serialises an entry: magic, version, kind, rows and columns as `u64`,
+/// then the row-major entries, all little endian
+fn encode(embedding: &Embedding) -> Vec<u8> {
+    let (kind, (rows, cols)) = match embedding {
+        Embedding::Categorical(embedding) => (CATEGORICAL, embedding.dim()),
+        Embedding::Continuous(embedding) => (CONTINUOUS, embedding.dim()),
+    };
+    let mut bytes = Vec::with_capacity(22 + 8 * rows * cols);
+    bytes.extend_from_slice(MAGIC);
+    bytes.push(FORMAT_VERSION);
+    bytes.push(kind);
+    bytes.extend_from_slice(&(rows as u64).to_le_bytes());
+    bytes.extend_from_slice(&(cols as u64).to_le_bytes());
+    match embedding {
+        Embedding::Categorical(embedding) => {
+            for &value in embedding {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        Embedding::Continuous(embedding) => {
+            for value in embedding {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+    }
+    bytes
+}
+
+/// Reads an entry written by [`encode`], or `None` if it is not a valid
+/// entry of `kind`
+fn decode(bytes: &[u8], kind: u8) -> Option<Embedding> {
+    let header = bytes.get(..22)?;
+    if &header[..4] != MAGIC || header[4] != FORMAT_VERSION || header[5] != kind {
+        return None;
+    }
+    let rows = u64::from_le_bytes(header[6..14].try_into().ok()?) as usize;
+    let cols = u64::from_le_bytes(header[14..22].try_into().ok()?) as usize;
+    let values = &bytes[22..];
+    if values.len() != rows.checked_mul(cols)?.checked_mul(8)? {
+        return None;
+    }
+    let words = values
+        .chunks_exact(8)
+        .map(|chunk| u64::from_le_bytes(chunk.try_into().expect("chunks of 8")));
+    match kind {
+        CATEGORICAL => {
+            let values = words.collect();
+            Array2::from_shape_vec((rows, cols), values)
+                .ok()
+                .map(Embedding::Categorical)
+        }
+        _ => {
+            let values = words.map(f64::from_bits).collect();
+            Array2::from_shape_vec((rows, cols), values)
+                .ok()
+                .map(Embedding::Continuous)
+        }
+    }
+}
+
+/// Writes `bytes` to a temporary file next to `path`, then renames it, so
+/// that readers never see a partial entry
+fn write_atomically(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let temporary = path.with_extension(format!(
+        "tmp-{}-{}",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    fs::write(&temporary, bytes)?;
+    fs::rename(&temporary, path).inspect_err(|_| {
+        let _ = fs::remove_file(&temporary);
+    })
+}
+
+fn cache_error(path: &Path, err: io::Error) -> WwlError {
+    WwlError::Cache(format!("{}: {}", path.display(), err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::{intern_graphs, MissingLabelStrategy};
+    use crate::propagation::categorical_propagation;
+    use crate::GraphType;
+    use petgraph::Graph;
+
+    fn graphs() -> Vec<WlGraph> {
+        let mut graph1: GraphType = Graph::new_undirected();
+        let n1 = graph1.add_node(Some(1));
+        let n2 = graph1.add_node(Some(2));
+        graph1.add_edge(n1, n2, ());
+        let mut graph2 = graph1.clone();
+        let n3 = graph2.add_node(Some(1));
+        graph2.add_edge(n2, n3, ());
+        intern_graphs(&[graph1, graph2], &MissingLabelStrategy::Error)
+            .unwrap()
+            .0
+    }
+
+    /// Whether two label sequences partition node positions alike
+    fn same_partition<A: Clone + PartialEq, B: Clone + PartialEq>(
+        a: &[Array2<A>],
+        b: &[Array2<B>],
+    ) -> bool {
+        fn flatten<T: Clone>(sequences: &[Array2<T>]) -> Vec<Vec<T>> {
+            let columns = sequences[0].ncols();
+            (0..columns)
+                .map(|h| {
+                    sequences
+                        .iter()
+                        .flat_map(|s| s.column(h).to_vec())
+                        .collect()
+                })
+                .collect()
+        }
+        flatten(a).iter().zip(flatten(b).iter()).all(|(x, y)| {
+            (0..x.len()).all(|i| (0..x.len()).all(|j| (x[i] == x[j]) == (y[i] == y[j])))
+        })
+    }
+
+    #[test]
+    fn test_hashed_matches_compressed_labels() {
+        let graphs = graphs();
+        let cache = EmbeddingCache::new();
+        let hashed = cache.categorical(&graphs, 3).unwrap();
+        assert!(same_partition(
+            &hashed,
+            &categorical_propagation(&graphs, 3)
+        ));
+
+        assert_eq!(cache.categorical(&graphs, 3).unwrap(), hashed);
+        assert_eq!(cache.stats(), CacheStats { hits: 2, misses: 2 });
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn test_keys_follow_labels_not_interned_ids() {
+        use crate::backend::{Backend, NativeBackend};
+        use crate::DistanceConfig;
+
+        let path = |labels: &[i32]| {
+            let mut graph: GraphType = Graph::new_undirected();
+            let nodes: Vec<_> = labels.iter().map(|&l| graph.add_node(Some(l))).collect();
+            for pair in nodes.windows(2) {
+                graph.add_edge(pair[0], pair[1], ());
+            }
+            graph
+        };
+        let intern = |graphs: &[GraphType]| {
+            intern_graphs(graphs, &MissingLabelStrategy::Error)
+                .unwrap()
+                .0
+        };
+        let cache = EmbeddingCache::new();
+        let backend = NativeBackend::with_cache(cache.clone());
+        let config = DistanceConfig::<()>::default();
+
+        let first = intern(&[path(&[1, 2]), path(&[1, 2, 2])]);
+        backend.compute_distance(&first, None, &config).unwrap();
+        assert_eq!(cache.stats().misses, 2);
+
+        // Same interned ids, other labels: nothing to reuse
+        let second = intern(&[path(&[5, 6]), path(&[5, 6, 6])]);
+        assert_eq!(first[0].labels(), second[0].labels());
+        let distances = backend.compute_distance(&second, None, &config).unwrap();
+        assert_eq!(
+            distances,
+            NativeBackend::new()
+                .compute_distance(&second, None, &config)
+                .unwrap()
+        );
+        assert_eq!(cache.stats(), CacheStats { hits: 0, misses: 4 });
+
+        // Other interned ids, same labels: the first entries are reused
+        let third = intern(&[path(&[2, 2]), path(&[1, 2, 2]), path(&[1, 2])]);
+        assert_ne!(first[0].labels(), third[2].labels());
+        backend.compute_distance(&third, None, &config).unwrap();
+        assert_eq!(cache.stats(), CacheStats { hits: 2, misses: 5 });
+    }
+
+    #[test]
+    fn test_keys_are_pinned() {
+        // Keys name files shared between runs and platforms
+        let graphs = graphs();
+        let labels = ContentLabels::of(&graphs[1]).unwrap();
+        assert_eq!(
+            key(CATEGORICAL, &graphs[1], &labels, None, 2),
+            0x0741_0ce5_e0a5_cec7
+        );
+    }
+
+    #[test]
+    fn test_capacity_evicts_oldest_entries() {
+        let graphs = graphs();
+        let cache = EmbeddingCache::new();
+        cache.categorical(&graphs, 1).unwrap();
+        cache.categorical(&graphs, 2).unwrap();
+        assert_eq!(cache.len(), 4);
+
+        cache.set_capacity(3);
+        assert_eq!((cache.capacity(), cache.len()), (Some(3), 3));
+        // The oldest entry is recomputed, the newest are kept
+        cache.categorical(&graphs[1..], 2).unwrap();
+        cache.categorical(&graphs[..1], 1).unwrap();
+        assert_eq!(cache.stats(), CacheStats { hits: 1, misses: 5 });
+        assert_eq!(cache.len(), 3);
+
+        cache.clear();
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_disk_entries_survive_the_process() {
+        let directory = std::env::temp_dir().join(format!("wwl-cache-test-{}", std::process::id()));
+        let graphs = graphs();
+        let features = vec![Array2::ones((2, 3)), Array2::zeros((3, 3))];
+
+        let cache = EmbeddingCache::with_directory(&directory).unwrap();
+        let embeddings = cache.continuous(&graphs, &features, 2).unwrap();
+        assert_eq!(fs::read_dir(&directory).unwrap().count(), 2);
+
+        // A fresh cache reads the entries back instead of recomputing them
+        let reopened = EmbeddingCache::with_directory(&directory).unwrap();
+        assert_eq!(
+            reopened.continuous(&graphs, &features, 2).unwrap(),
+            embeddings
+        );
+        assert_eq!(reopened.stats(), CacheStats { hits: 2, misses: 0 });
+
+        // Other features and iteration counts are other entries
+        reopened.continuous(&graphs, &features, 3).unwrap();
+        assert_eq!(reopened.stats().misses, 2);
+
+        // Corrupt entries are recomputed
+        for entry in fs::read_dir(&directory).unwrap() {
+            fs::write(entry.unwrap().path(), b"garbage").unwrap();
+        }
+        let recovered = EmbeddingCache::with_directory(&directory).unwrap();
+        assert_eq!(
+            recovered.continuous(&graphs, &features, 2).unwrap(),
+            embeddings
+        );
+        assert_eq!(recovered.stats().misses, 2);
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
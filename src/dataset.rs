@@ -0,0 +1,469 @@
+//! Reader for the TU Dortmund graph benchmark format
+//!
+//! A dataset `DS` is a directory of text files sharing the `DS_` prefix:
+//!
+//! - `DS_A.txt`: one edge per line, `row, col`, with node ids counted from 1
+//!   across the whole dataset
+//! - `DS_graph_indicator.txt`: the graph id of every node, counted from 1
+//! - `DS_graph_labels.txt`: the class label of every graph
+//! - `DS_node_labels.txt`, `DS_node_attributes.txt`: optional node labels and
+//!   comma-separated node attributes
+//! - `DS_edge_labels.txt`: optional labels of the edges of `DS_A.txt`
+//!
+//! See <https://chrsmrrs.github.io/datasets/docs/format/> for the format.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use ndarray::Array2;
+use petgraph::graph::NodeIndex;
+use petgraph::{Graph, Undirected};
+
+use crate::{GraphType, WwlError};
+
+/// Graphs of a TU dataset with their labels and attributes
+///
+/// Graphs are undirected; edges listed in both directions, as the format
+/// does, are added once, and must then carry the same label. Nodes keep
+/// the order of the dataset files, so row `i` of a node attribute matrix
+/// belongs to `NodeIndex::new(i)`.
+#[derive(Clone, Debug)]
+pub struct TuDataset {
+    /// Dataset name, the prefix of its files
+    pub name: String,
+    /// Graphs whose nodes carry their label, or `None` without node labels
+    pub graphs: Vec<GraphType>,
+    /// Class label of every graph
+    pub graph_labels: Option<Vec<i32>>,
+    /// One `node_count × dims` attribute matrix per graph
+    pub node_attributes: Option<Vec<Array2<f64>>>,
+    /// Labels of the edges of every graph, indexed by `EdgeIndex`
+    pub edge_labels: Option<Vec<Vec<i32>>>,
+}
+
+impl TuDataset {
+    /// Reads the dataset in `directory`, named after its `*_A.txt` file
+    pub fn load(directory: impl AsRef<Path>) -> Result<Self, WwlError> {
+        let directory = directory.as_ref();
+        let entries = fs::read_dir(directory).map_err(|err| io_error(directory, err))?;
+        let mut names = Vec::new();
+        for entry in entries {
+            let entry = entry.map_err(|err| io_error(directory, err))?;
+            if let Some(name) = entry
+                .file_name()
+                .to_str()
+                .and_then(|f| f.strip_suffix("_A.txt"))
+            {
+                names.push(name.to_string());
+            }
+        }
+        match names.as_slice() {
+            [name] => Self::load_named(directory, name),
+            [] => Err(WwlError::Dataset(format!(
+                "{}: no *_A.txt file",
+                directory.display()
+            ))),
+            _ => Err(WwlError::Dataset(format!(
+                "{}: several datasets ({}), use TuDataset::load_named",
+                directory.display(),
+                names.join(", ")
+            ))),
+        }
+    }
+
+    /// Reads dataset `name` from the files `name_*.txt` in `directory`
+    pub fn load_named(directory: impl AsRef<Path>, name: &str) -> Result<Self, WwlError> {
+        let file = |suffix: &str| directory.as_ref().join(format!("{}_{}.txt", name, suffix));
+
+        let indicator: Vec<(usize, usize)> = read_values(&file("graph_indicator"))?;
+        if let Some(&(line, _)) = indicator.iter().find(|&&(_, graph)| graph == 0) {
+            return Err(parse_error(
+                &file("graph_indicator"),
+                line,
+                "graph ids start at 1",
+            ));
+        }
+        let indicator: Vec<usize> = indicator.into_iter().map(|(_, graph)| graph).collect();
+        let num_nodes = indicator.len();
+        let num_graphs = indicator.iter().copied().max().unwrap_or(0);
+
+        let node_labels: Option<Vec<i32>> = read_optional(&file("node_labels"), num_nodes)?;
+        let graph_labels: Option<Vec<i32>> = read_optional(&file("graph_labels"), num_graphs)?;
+        let attributes = read_attributes(&file("node_attributes"), num_nodes)?;
+
+        let mut graphs: Vec<GraphType> = (0..num_graphs).map(|_| Graph::new_undirected()).collect();
+        let nodes: Vec<NodeIndex> = indicator
+            .iter()
+            .enumerate()
+            .map(|(node, &graph)| {
+                graphs[graph - 1].add_node(node_labels.as_ref().map(|labels| labels[node]))
+            })
+            .collect();
+
+        let edges_file = file("A");
+        let edges = read_edges(&edges_file, num_nodes)?;
+        let edge_label_values: Option<Vec<i32>> = read_optional(&file("edge_labels"), edges.len())?;
+        let mut edge_labels = edge_label_values
+            .as_ref()
+            .map(|_| vec![Vec::new(); num_graphs]);
+        // First line and label of every undirected edge
+        let mut seen = HashMap::new();
+        for (edge, &(line, (a, b))) in edges.iter().enumerate() {
+            let graph = indicator[a] - 1;
+            if indicator[b] - 1 != graph {
+                return Err(parse_error(&edges_file, line, "edge joins two graphs"));
+            }
+            let label = edge_label_values.as_ref().map(|values| values[edge]);
+            if let Some(&(first, known)) = seen.get(&(a.min(b), a.max(b))) {
+                if let (Some(known), Some(label)) = (known, label) {
+                    if known != label {
+                        return Err(parse_error(
+                            &edges_file,
+                            line,
+                            &format!(
+                                "edge label {} conflicts with label {} of the same edge at line {}",
+                                label,
+                                known,
+                                first + 1
+                            ),
+                        ));
+                    }
+                }
+                continue;
+            }
+            seen.insert((a.min(b), a.max(b)), (line, label));
+            graphs[graph].add_edge(nodes[a], nodes[b], ());
+            if let (Some(edge_labels), Some(label)) = (&mut edge_labels, label) {
+                edge_labels[graph].push(label);
+            }
+        }
+
+        let node_attributes = attributes.map(|attributes| {
+            let mut matrices: Vec<Array2<f64>> = graphs
+                .iter()
+                .map(|graph| Array2::zeros((graph.node_count(), attributes.ncols())))
+                .collect();
+            for (node, row) in attributes.outer_iter().enumerate() {
+                let graph = indicator[node] - 1;
+                matrices[graph].row_mut(nodes[node].index()).assign(&row);
+            }
+            matrices
+        });
+
+        Ok(TuDataset {
+            name: name.to_string(),
+            graphs,
+            graph_labels,
+            node_attributes,
+            edge_labels,
+        })
+    }
+
+    /// Number of graphs
+    pub fn len(&self) -> usize {
+        self.graphs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.graphs.is_empty()
+    }
+
+    /// Attribute `dimension` of every node as one zero-padded row per graph
+    ///
+    /// This is the node feature layout of
+    /// [`WWLKernel::compute_kernel_continuous`](crate::WWLKernel::compute_kernel_continuous).
+    /// Returns `None` without node attributes or if `dimension` is out of
+    /// range.
+    pub fn node_features(&self, dimension: usize) -> Option<Array2<f64>> {
+        let attributes = self.node_attributes.as_ref()?;
+        if attributes.iter().any(|matrix| dimension >= matrix.ncols()) {
+            return None;
+        }
+        let width = self.graphs.iter().map(Graph::node_count).max().unwrap_or(0);
+        let mut features = Array2::zeros((self.len(), width));
+        for (mut row, matrix) in features.outer_iter_mut().zip(attributes) {
+            for (node, &value) in matrix.column(dimension).iter().enumerate() {
+                row[node] = value;
+            }
+        }
+        Some(features)
+    }
+
+    /// Copies of the graphs with edge labels as edge weights, read as edge
+    /// labels by categorical propagation
+    pub fn graphs_with_edge_labels(&self) -> Option<Vec<Graph<Option<i32>, i32, Undirected>>> {
+        let edge_labels = self.edge_labels.as_ref()?;
+        Some(
+            self.graphs
+                .iter()
+                .zip(edge_labels)
+                .map(|(graph, labels)| graph.map(|_, &node| node, |edge, _| labels[edge.index()]))
+                .collect(),
+        )
+    }
+}
+
+/// Reads one value per non-empty line, with the 0-based number of that line
+fn read_values<T: FromStr>(path: &Path) -> Result<Vec<(usize, T)>, WwlError> {
+    let text = fs::read_to_string(path).map_err(|err| io_error(path, err))?;
+    lines(&text)
+        .map(|(line, value)| {
+            let value = value
+                .parse()
+                .map_err(|_| parse_error(path, line, &format!("invalid value `{}`", value)))?;
+            Ok((line, value))
+        })
+        .collect()
+}
+
+/// Reads an optional per-item file, which must have one line per item
+fn read_optional<T: FromStr>(path: &Path, expected: usize) -> Result<Option<Vec<T>>, WwlError> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let values: Vec<T> = read_values(path)?
+        .into_iter()
+        .map(|(_, value)| value)
+        .collect();
+    check_count(path, expected, values.len())?;
+    Ok(Some(values))
+}
+
+/// Reads the node attributes as one row per node of the dataset
+fn read_attributes(path: &Path, num_nodes: usize) -> Result<Option<Array2<f64>>, WwlError> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let text = fs::read_to_string(path).map_err(|err| io_error(path, err))?;
+    let mut values = Vec::new();
+    let mut dims = None;
+    let mut rows = 0;
+    for (line, row) in lines(&text) {
+        let start = values.len();
+        for value in row.split(',').map(str::trim) {
+            let value = value
+                .parse()
+                .map_err(|_| parse_error(path, line, &format!("invalid attribute `{}`", value)))?;
+            values.push(value);
+        }
+        let found = values.len() - start;
+        let expected = *dims.get_or_insert(found);
+        if found != expected {
+            return Err(parse_error(
+                path,
+                line,
+                &format!("expected {} attributes, found {}", expected, found),
+            ));
+        }
+        rows += 1;
+    }
+    check_count(path, num_nodes, rows)?;
+    let attributes = Array2::from_shape_vec((rows, dims.unwrap_or(0)), values)
+        .map_err(|err| WwlError::Dataset(format!("{}: {}", path.display(), err)))?;
+    Ok(Some(attributes))
+}
+
+/// Endpoints of an edge as 0-based node ids
+type Edge = (usize, usize);
+
+/// Reads the edge list, with the 0-based number of the line of each edge
+fn read_edges(path: &Path, num_nodes: usize) -> Result<Vec<(usize, Edge)>, WwlError> {
+    let text = fs::read_to_string(path).map_err(|err| io_error(path, err))?;
+    lines(&text)
+        .map(|(line, edge)| {
+            let ids: Vec<usize> = edge
+                .split(',')
+                .map(|id| id.trim().parse::<usize>())
+                .collect::<Result<_, _>>()
+                .map_err(|_| parse_error(path, line, &format!("invalid edge `{}`", edge)))?;
+            match ids[..] {
+                [a, b] if (1..=num_nodes).contains(&a) && (1..=num_nodes).contains(&b) => {
+                    Ok((line, (a - 1, b - 1)))
+                }
+                [_, _] => Err(parse_error(path, line, "node id out of range")),
+                _ => Err(parse_error(path, line, "expected two node ids")),
+            }
+        })
+        .collect()
+}
+
+/// Non-empty lines with their 0-based line number, trimmed
+fn lines(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    text.lines()
+        .map(str::trim)
+        .enumerate()
+        .filter(|(_, line)| !line.is_empty())
+}
+
+fn check_count(path: &Path, expected: usize, found: usize) -> Result<(), WwlError> {
+    if expected != found {
+        return Err(WwlError::ShapeMismatch {
+            context: format!("Lines of {}", path.display()),
+            expected,
+            found,
+        });
+    }
+    Ok(())
+}
+
+fn parse_error(path: &Path, line: usize, message: &str) -> WwlError {
+    WwlError::Dataset(format!("{}:{}: {}", path.display(), line + 1, message))
+}
+
+fn io_error(path: &Path, err: std::io::Error) -> WwlError {
+    WwlError::Dataset(format!("{}: {}", path.display(), err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// Writes the files of dataset `TOY` to a fresh directory
+    fn write_dataset(test: &str, files: &[(&str, &str)]) -> PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("wwl-dataset-{}-{}", test, std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        for (suffix, contents) in files {
+            fs::write(directory.join(format!("TOY_{}.txt", suffix)), contents).unwrap();
+        }
+        directory
+    }
+
+    const EDGES: &str = "1, 2\n2, 1\n2, 3\n3, 2\n3, 1\n1, 3\n4, 5\n5, 4\n";
+
+    /// A labeled triangle and a labeled edge with all optional files
+    fn toy_dataset(test: &str) -> TuDataset {
+        let directory = write_dataset(
+            test,
+            &[
+                ("A", EDGES),
+                ("graph_indicator", "1\n1\n1\n2\n2\n"),
+                ("graph_labels", "1\n-1\n"),
+                ("node_labels", "0\n1\n0\n2\n2\n"),
+                (
+                    "node_attributes",
+                    "0.5, 1\n1.5, 1\n2.5, 1\n3.5, 0\n4.5, 0\n",
+                ),
+                ("edge_labels", "1\n1\n2\n2\n1\n1\n3\n3\n"),
+            ],
+        );
+        let dataset = TuDataset::load(&directory).unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+        dataset
+    }
+
+    #[test]
+    fn test_load_dataset() {
+        let dataset = toy_dataset("load");
+        assert_eq!(dataset.name, "TOY");
+        assert_eq!(dataset.len(), 2);
+        assert_eq!(dataset.graphs[0].edge_count(), 3);
+        assert_eq!(dataset.graphs[1].edge_count(), 1);
+        assert_eq!(dataset.graphs[1][NodeIndex::new(0)], Some(2));
+        assert_eq!(dataset.graph_labels, Some(vec![1, -1]));
+        assert_eq!(dataset.edge_labels, Some(vec![vec![1, 2, 1], vec![3]]));
+
+        let attributes = dataset.node_attributes.as_ref().unwrap();
+        assert_eq!(attributes[1], ndarray::array![[3.5, 0.0], [4.5, 0.0]]);
+        assert_eq!(
+            dataset.node_features(0).unwrap(),
+            ndarray::array![[0.5, 1.5, 2.5], [3.5, 4.5, 0.0]]
+        );
+        assert!(dataset.node_features(2).is_none());
+
+        let labeled = dataset.graphs_with_edge_labels().unwrap();
+        assert_eq!(
+            labeled[0].edge_weights().copied().collect::<Vec<_>>(),
+            [1, 2, 1]
+        );
+    }
+
+    #[test]
+    fn test_malformed_dataset() {
+        // Unlabeled nodes are left as `None`
+        let directory = write_dataset(
+            "malformed",
+            &[("A", EDGES), ("graph_indicator", "1\n1\n1\n2\n2\n")],
+        );
+        let dataset = TuDataset::load(&directory).unwrap();
+        assert_eq!(dataset.graphs[0][NodeIndex::new(0)], None);
+        assert!(dataset.graph_labels.is_none());
+
+        // One node label short
+        fs::write(directory.join("TOY_node_labels.txt"), "0\n1\n0\n2\n").unwrap();
+        assert!(matches!(
+            TuDataset::load(&directory),
+            Err(WwlError::ShapeMismatch {
+                expected: 5,
+                found: 4,
+                ..
+            })
+        ));
+        fs::remove_file(directory.join("TOY_node_labels.txt")).unwrap();
+
+        // Errors point at file lines, counting blank ones
+        fs::write(directory.join("TOY_A.txt"), "1, 2\n\n\n1, 4\n").unwrap();
+        assert!(matches!(
+            TuDataset::load(&directory),
+            Err(WwlError::Dataset(message)) if message.ends_with(":4: edge joins two graphs")
+        ));
+        fs::write(directory.join("TOY_A.txt"), "\n1, 2\n\n1, 9\n").unwrap();
+        assert!(matches!(
+            TuDataset::load(&directory),
+            Err(WwlError::Dataset(message)) if message.ends_with(":4: node id out of range")
+        ));
+
+        // Both directions of an edge must carry the same label
+        fs::write(directory.join("TOY_A.txt"), "1, 2\n2, 3\n\n2, 1\n").unwrap();
+        fs::write(directory.join("TOY_edge_labels.txt"), "1\n2\n1\n").unwrap();
+        TuDataset::load(&directory).unwrap();
+        fs::write(directory.join("TOY_edge_labels.txt"), "1\n2\n2\n").unwrap();
+        assert!(matches!(
+            TuDataset::load(&directory),
+            Err(WwlError::Dataset(message)) if message.ends_with(
+                ":4: edge label 2 conflicts with label 1 of the same edge at line 1"
+            )
+        ));
+        fs::remove_file(directory.join("TOY_edge_labels.txt")).unwrap();
+
+        fs::write(
+            directory.join("TOY_graph_indicator.txt"),
+            "1\n\n1\n0\n2\n2\n",
+        )
+        .unwrap();
+        assert!(matches!(
+            TuDataset::load(&directory),
+            Err(WwlError::Dataset(message)) if message.ends_with(":4: graph ids start at 1")
+        ));
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[cfg(feature = "native")]
+    #[test]
+    fn test_kernels_on_dataset() {
+        use crate::{KernelConfig, WWLKernel};
+
+        let dataset = toy_dataset("kernels");
+        let kernel = WWLKernel::native();
+        let config = KernelConfig::default();
+
+        let categorical = kernel
+            .compute_kernel_categorical(&dataset.graphs, &config)
+            .unwrap();
+        let edge_labeled = kernel
+            .compute_kernel_categorical(&dataset.graphs_with_edge_labels().unwrap(), &config)
+            .unwrap();
+        let features = dataset.node_features(0).unwrap();
+        let continuous = kernel
+            .compute_kernel_continuous(&dataset.graphs, &features, &config)
+            .unwrap();
+        for matrix in [categorical, edge_labeled, continuous] {
+            assert_eq!(matrix.dim(), (2, 2));
+            assert!((matrix[[0, 0]] - 1.0).abs() < 1e-9);
+            assert!(matrix[[0, 1]] < 1.0);
+        }
+    }
+}
@@ -0,0 +1,152 @@
+//! Error type shared by all backends
+
+use std::fmt;
+use std::time::Duration;
+
+/// Errors returned by [`WWLKernel`](crate::WWLKernel) and its backends
+#[derive(Debug, Clone, PartialEq)]
+pub enum WwlError {
+    /// A Python module required by the backend could not be imported
+    ModuleNotFound { module: String, message: String },
+    /// A call into Python raised an exception
+    Python {
+        exception_type: String,
+        message: String,
+        traceback: Option<String>,
+    },
+    /// Input arrays do not match the graphs they describe
+    ShapeMismatch {
+        context: String,
+        expected: usize,
+        found: usize,
+    },
+    /// A backend result could not be converted into an ndarray
+    Extraction(String),
+    /// The configuration was rejected before running any computation
+    InvalidConfig(String),
+    /// Graphs at these collection indices contain unlabeled nodes
+    MissingLabels { graphs: Vec<usize> },
+    /// An edge weight is negative or not finite
+    InvalidEdgeWeight { graph: usize, weight: f64 },
+    /// A node feature is not finite; `node` is the petgraph index
+    InvalidNodeFeature {
+        graph: usize,
+        node: usize,
+        value: f64,
+    },
+    /// The backend cannot handle a feature of the input
+    Unsupported(String),
+    /// A Python worker process could not be started or stopped responding
+    Worker(String),
+    /// A Python worker process did not answer a call within the configured
+    /// timeout and was stopped
+    WorkerTimeout(Duration),
+    /// The Python environment has package versions known not to work
+    IncompatibleEnvironment(Vec<String>),
+    /// The embedding cache could not read or write its directory
+    Cache(String),
+    /// Sinkhorn stopped at `max_iterations` before matching the marginals of
+    /// this pair of graphs up to the tolerance
+    NotConverged {
+        graphs: (usize, usize),
+        iterations: usize,
+        error: f64,
+    },
+    /// A dataset file could not be read or parsed
+    Dataset(String),
+    /// Labels serve as features, but this node's label has no numeric value;
+    /// `node` is its petgraph index
+    NonNumericLabel { graph: usize, node: usize },
+}
+
+impl fmt::Display for WwlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WwlError::ModuleNotFound { module, message } => {
+                write!(
+                    f,
+                    "Failed to import Python module `{}`: {}",
+                    module, message
+                )
+            }
+            WwlError::Python {
+                exception_type,
+                message,
+                traceback,
+            } => {
+                write!(f, "Python raised {}: {}", exception_type, message)?;
+                if let Some(traceback) = traceback {
+                    write!(f, "\n{}", traceback.trim_end())?;
+                }
+                Ok(())
+            }
+            WwlError::ShapeMismatch {
+                context,
+                expected,
+                found,
+            } => write!(f, "{}: expected {}, found {}", context, expected, found),
+            WwlError::Extraction(message) => write!(f, "Result extraction failed: {}", message),
+            WwlError::InvalidConfig(message) => write!(f, "Invalid configuration: {}", message),
+            WwlError::MissingLabels { graphs } => {
+                write!(f, "Unlabeled nodes in graphs {:?}", graphs)
+            }
+            WwlError::InvalidEdgeWeight { graph, weight } => {
+                write!(f, "Invalid edge weight {} in graph {}", weight, graph)
+            }
+            WwlError::InvalidNodeFeature { graph, node, value } => write!(
+                f,
+                "Invalid feature {} of node {} in graph {}",
+                value, node, graph
+            ),
+            WwlError::Unsupported(message) => write!(f, "Unsupported input: {}", message),
+            WwlError::Worker(message) => write!(f, "Python worker failed: {}", message),
+            WwlError::WorkerTimeout(timeout) => write!(
+                f,
+                "Python worker did not answer within {:?} and was stopped",
+                timeout
+            ),
+            WwlError::IncompatibleEnvironment(issues) => {
+                write!(f, "Incompatible Python environment: {}", issues.join("; "))
+            }
+            WwlError::Cache(message) => write!(f, "Embedding cache failed: {}", message),
+            WwlError::NotConverged {
+                graphs,
+                iterations,
+                error,
+            } => write!(
+                f,
+                "Sinkhorn did not converge for graphs {} and {} after {} iterations \
+                 (marginal error {:e}); raise max_iterations or tolerance",
+                graphs.0, graphs.1, iterations, error
+            ),
+            WwlError::Dataset(message) => write!(f, "Invalid dataset: {}", message),
+            WwlError::NonNumericLabel { graph, node } => write!(
+                f,
+                "Node {} of graph {} has no numeric label to use as a continuous feature",
+                node, graph
+            ),
+        }
+    }
+}
+
+impl std::error::Error for WwlError {}
+
+#[cfg(feature = "python")]
+impl From<pyo3::PyErr> for WwlError {
+    fn from(err: pyo3::PyErr) -> Self {
+        use pyo3::types::{PyTracebackMethods, PyTypeMethods};
+
+        pyo3::Python::attach(|py| {
+            let exception_type = err
+                .get_type(py)
+                .name()
+                .map(|name| name.to_string())
+                .unwrap_or_else(|_| "Exception".to_string());
+            WwlError::Python {
+                exception_type,
+                message: err.value(py).to_string(),
+                traceback: err.traceback(py).and_then(|tb| tb.format().ok()),
+            }
+        })
+    }
+}
@@ -0,0 +1,1122 @@
+//! Graph input normalisation
+//!
+//! The kernel entry points accept any petgraph container implementing the
+//! visit traits below. Each graph is normalised into a [`WlGraph`] before it
+//! reaches a backend.
+
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+use petgraph::visit::{
+    EdgeRef, GraphProp, IntoEdges, IntoNeighbors, IntoNodeIdentifiers, IntoNodeReferences,
+    NodeCount, NodeIndexable, NodeRef,
+};
+
+use crate::WwlError;
+
+/// Categorical label carried by a node or edge weight
+///
+/// Any `Hash + Eq` value implementing [`LabelValue`] can serve as a label.
+/// Edge weights are read through the same trait, with `()` leaving edges
+/// unlabeled. Implement this trait for custom weights, for example an enum of
+/// atom types:
+///
+/// ```
+/// use wwl::{LabelValue, NodeLabel};
+///
+/// #[derive(Clone, PartialEq, Eq, Hash)]
+/// enum Atom {
+///     Carbon,
+///     Oxygen,
+/// }
+///
+/// impl NodeLabel for Atom {
+///     type Label = Atom;
+///
+///     fn node_label(&self) -> Option<Atom> {
+///         Some(self.clone())
+///     }
+/// }
+///
+/// // Atom types have no numeric value
+/// impl LabelValue for Atom {}
+/// ```
+pub trait NodeLabel {
+    type Label: Hash + Eq + Clone + LabelValue + 'static;
+
+    /// Returns the label of the weight, or `None` if it carries none
+    fn node_label(&self) -> Option<Self::Label>;
+}
+
+impl<T: Hash + Eq + Clone + LabelValue + 'static> NodeLabel for Option<T> {
+    type Label = T;
+
+    fn node_label(&self) -> Option<T> {
+        self.clone()
+    }
+}
+
+impl NodeLabel for () {
+    type Label = ();
+
+    fn node_label(&self) -> Option<()> {
+        None
+    }
+}
+
+impl NodeLabel for &'static str {
+    type Label = &'static str;
+
+    fn node_label(&self) -> Option<&'static str> {
+        Some(self)
+    }
+}
+
+macro_rules! impl_node_label {
+    ($($ty:ty),*) => {
+        $(
+            impl NodeLabel for $ty {
+                type Label = $ty;
+
+                fn node_label(&self) -> Option<$ty> {
+                    Some(self.clone())
+                }
+            }
+        )*
+    };
+}
+
+impl_node_label!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize, bool, char, String);
+
+impl<A, B> NodeLabel for (A, B)
+where
+    A: Hash + Eq + Clone + 'static,
+    B: Hash + Eq + Clone + 'static,
+{
+    type Label = (A, B);
+
+    fn node_label(&self) -> Option<(A, B)> {
+        Some(self.clone())
+    }
+}
+
+impl<A, B, C> NodeLabel for (A, B, C)
+where
+    A: Hash + Eq + Clone + 'static,
+    B: Hash + Eq + Clone + 'static,
+    C: Hash + Eq + Clone + 'static,
+{
+    type Label = (A, B, C);
+
+    fn node_label(&self) -> Option<(A, B, C)> {
+        Some(self.clone())
+    }
+}
+
+/// Numeric value of a label, read as a node feature under
+/// [`DistanceConfig::enforce_continuous`](crate::DistanceConfig::enforce_continuous)
+///
+/// Integers are their own value. Other labels have none by default, and
+/// graphs carrying them are rejected with [`WwlError::NonNumericLabel`] when
+/// labels serve as features.
+pub trait LabelValue {
+    fn label_value(&self) -> Option<f64> {
+        None
+    }
+}
+
+macro_rules! impl_label_value {
+    ($($ty:ty),*) => {
+        $(
+            impl LabelValue for $ty {
+                fn label_value(&self) -> Option<f64> {
+                    Some(*self as f64)
+                }
+            }
+        )*
+    };
+}
+
+impl_label_value!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+
+impl LabelValue for () {}
+impl LabelValue for bool {}
+impl LabelValue for char {}
+impl LabelValue for String {}
+impl LabelValue for &'static str {}
+impl<A, B> LabelValue for (A, B) {}
+impl<A, B, C> LabelValue for (A, B, C) {}
+
+impl<T: LabelValue> LabelValue for Option<T> {
+    fn label_value(&self) -> Option<f64> {
+        self.as_ref().and_then(LabelValue::label_value)
+    }
+}
+
+/// Real-valued strength carried by an edge weight
+///
+/// Continuous propagation on weighted graphs averages neighbour features with
+/// these strengths as coefficients. `()` edges have strength one.
+pub trait EdgeWeight {
+    fn edge_weight(&self) -> f64;
+}
+
+impl EdgeWeight for () {
+    fn edge_weight(&self) -> f64 {
+        1.0
+    }
+}
+
+macro_rules! impl_edge_weight {
+    ($($ty:ty),*) => {
+        $(
+            impl EdgeWeight for $ty {
+                fn edge_weight(&self) -> f64 {
+                    *self as f64
+                }
+            }
+        )*
+    };
+}
+
+impl_edge_weight!(f32, f64, i8, i16, i32, i64, u8, u16, u32, u64);
+
+/// Interning table mapping node labels to compact integer ids
+///
+/// Ids are handed out as `0, 1, 2, ...` in order of first appearance, so the
+/// same collection always yields the same mapping. Backends only ever see the
+/// ids; use [`LabelDictionary::label`] to trace an id back to its label.
+#[derive(Clone, Debug)]
+pub struct LabelDictionary<L> {
+    ids: HashMap<L, usize>,
+    labels: Vec<L>,
+}
+
+impl<L: Hash + Eq + Clone> LabelDictionary<L> {
+    pub fn new() -> Self {
+        LabelDictionary {
+            ids: HashMap::new(),
+            labels: Vec::new(),
+        }
+    }
+
+    /// Returns the id of `label`, assigning the next free id if it is new
+    pub fn intern(&mut self, label: L) -> usize {
+        if let Some(&id) = self.ids.get(&label) {
+            return id;
+        }
+        let id = self.labels.len();
+        self.ids.insert(label.clone(), id);
+        self.labels.push(label);
+        id
+    }
+
+    /// Id of an already interned label
+    pub fn id(&self, label: &L) -> Option<usize> {
+        self.ids.get(label).copied()
+    }
+
+    /// Label interned under `id`
+    pub fn label(&self, id: usize) -> Option<&L> {
+        self.labels.get(id)
+    }
+
+    /// Interned labels, indexed by id
+    pub fn labels(&self) -> &[L] {
+        &self.labels
+    }
+
+    pub fn len(&self) -> usize {
+        self.labels.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.labels.is_empty()
+    }
+}
+
+impl<L: Hash + Eq + Clone> Default for LabelDictionary<L> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// How unlabeled nodes of a labeled collection are treated
+///
+/// The strategy applies to every graph of the collection alike, whether the
+/// graph is fully, partially or not labeled at all. `L` is the
+/// [`NodeLabel::Label`] type of the graphs, so a constant label of the wrong
+/// type is rejected at compile time.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum MissingLabelStrategy<L = i32> {
+    /// Reject the collection, reporting the graphs with unlabeled nodes
+    Error,
+    /// Give unlabeled nodes this regular label
+    Constant(L),
+    /// Use the node degree, kept apart from regular labels
+    #[default]
+    Degree,
+    /// Give unlabeled nodes one dedicated label that no regular label equals
+    Unknown,
+}
+
+impl<L> MissingLabelStrategy<L> {
+    /// The same strategy without its constant label, for backends, which
+    /// receive graphs whose missing labels are already resolved
+    pub(crate) fn erase_label(&self) -> MissingLabelStrategy<()> {
+        match self {
+            MissingLabelStrategy::Error => MissingLabelStrategy::Error,
+            MissingLabelStrategy::Constant(_) => MissingLabelStrategy::Constant(()),
+            MissingLabelStrategy::Degree => MissingLabelStrategy::Degree,
+            MissingLabelStrategy::Unknown => MissingLabelStrategy::Unknown,
+        }
+    }
+}
+
+/// Label of a node before the first relabeling step
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum InitialLabel {
+    /// Interned label id, see [`LabelDictionary`]
+    Label(usize),
+    /// Node degree, standing in for a missing label
+    Degree(usize),
+    /// Shared stand-in for missing labels
+    Unknown,
+}
+
+/// Graph normalised for the backends
+///
+/// Nodes are renumbered `0..node_count` in increasing petgraph index order,
+/// which is also the row order expected for per-graph node features. Holes
+/// left by removed nodes in containers such as `StableGraph` are skipped.
+///
+/// Undirected graphs list every edge under [`WlGraph::neighbors`]. Directed
+/// graphs list outgoing edges there and incoming edges under
+/// [`WlGraph::in_neighbors`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct WlGraph {
+    labels: Vec<InitialLabel>,
+    indices: Vec<usize>,
+    outgoing: Adjacency,
+    incoming: Adjacency,
+    directed: bool,
+    missing: usize,
+    label_hashes: LabelHashes,
+    /// [`LabelValue`] of the labels behind the interned ids that have one
+    label_values: HashMap<usize, f64>,
+}
+
+/// Stable hashes of the labels behind the interned ids a graph uses
+///
+/// Interned ids depend on the collection a graph was interned with; the
+/// hashes identify the labels themselves.
+#[derive(Clone, Debug, Default, PartialEq)]
+struct LabelHashes {
+    nodes: HashMap<usize, u64>,
+    edges: HashMap<usize, u64>,
+}
+
+/// Edge lists of every node, with the label and strength of each edge
+#[derive(Clone, Debug, PartialEq)]
+struct Adjacency {
+    neighbors: Vec<Vec<usize>>,
+    labels: Vec<Vec<Option<usize>>>,
+    weights: Vec<Vec<f64>>,
+}
+
+impl Adjacency {
+    fn with_nodes(node_count: usize) -> Self {
+        Adjacency {
+            neighbors: vec![Vec::new(); node_count],
+            labels: vec![Vec::new(); node_count],
+            weights: vec![Vec::new(); node_count],
+        }
+    }
+
+    fn push(&mut self, node: usize, neighbor: usize, label: Option<usize>, weight: f64) {
+        self.neighbors[node].push(neighbor);
+        self.labels[node].push(label);
+        self.weights[node].push(weight);
+    }
+
+    /// Same edges seen from their other endpoint
+    fn reversed(&self) -> Self {
+        let mut reversed = Adjacency::with_nodes(self.neighbors.len());
+        for node in 0..self.neighbors.len() {
+            for (index, &neighbor) in self.neighbors[node].iter().enumerate() {
+                reversed.push(
+                    neighbor,
+                    node,
+                    self.labels[node][index],
+                    self.weights[node][index],
+                );
+            }
+        }
+        reversed
+    }
+}
+
+impl WlGraph {
+    /// Captures the structure of a graph, labeling every node by its degree
+    ///
+    /// Neighbours are those reported by `IntoNeighbors`; for a directed
+    /// container these are the outgoing edges, and incoming edges are derived
+    /// from them.
+    pub fn from_topology<G>(graph: G) -> Self
+    where
+        G: IntoNodeIdentifiers + IntoNeighbors + NodeIndexable + NodeCount + GraphProp,
+    {
+        let (nodes, positions) = ordered_nodes(graph);
+        let mut outgoing = Adjacency::with_nodes(nodes.len());
+        for (position, &node) in nodes.iter().enumerate() {
+            for neighbor in graph.neighbors(node) {
+                outgoing.push(position, positions[graph.to_index(neighbor)], None, 1.0);
+            }
+        }
+        Self::from_adjacency(indices(graph, &nodes), outgoing, graph.is_directed())
+    }
+
+    /// Captures the structure of a graph and the strengths of its edge weights
+    pub fn from_weighted<G>(graph: G) -> Self
+    where
+        G: IntoNodeIdentifiers + IntoEdges + NodeIndexable + NodeCount + GraphProp,
+        G::EdgeWeight: EdgeWeight,
+    {
+        let (nodes, positions) = ordered_nodes(graph);
+        let mut outgoing = Adjacency::with_nodes(nodes.len());
+        for (position, &node) in nodes.iter().enumerate() {
+            for edge in graph.edges(node) {
+                outgoing.push(
+                    position,
+                    positions[graph.to_index(edge.target())],
+                    None,
+                    edge.weight().edge_weight(),
+                );
+            }
+        }
+        Self::from_adjacency(indices(graph, &nodes), outgoing, graph.is_directed())
+    }
+
+    /// Captures the structure of a graph and the labels of its node and edge
+    /// weights
+    ///
+    /// Labels are interned into `dictionary` and edge labels into
+    /// `edge_dictionary` in node index order; share both dictionaries across a
+    /// collection so equal labels get equal ids. Under
+    /// [`MissingLabelStrategy::Error`] unlabeled nodes are kept as
+    /// [`InitialLabel::Unknown`], and [`intern_graphs`] rejects the collection.
+    pub fn from_labeled<G>(
+        graph: G,
+        dictionary: &mut LabelDictionary<<G::NodeWeight as NodeLabel>::Label>,
+        edge_dictionary: &mut LabelDictionary<<G::EdgeWeight as NodeLabel>::Label>,
+        missing: &MissingLabelStrategy<<G::NodeWeight as NodeLabel>::Label>,
+    ) -> Result<Self, WwlError>
+    where
+        G: IntoNodeReferences + IntoEdges + NodeIndexable + NodeCount + GraphProp,
+        G::NodeWeight: NodeLabel,
+        G::EdgeWeight: NodeLabel,
+    {
+        let (nodes, positions) = ordered_nodes(graph);
+        let mut label_hashes = LabelHashes::default();
+        let mut outgoing = Adjacency::with_nodes(nodes.len());
+        for (position, &node) in nodes.iter().enumerate() {
+            for edge in graph.edges(node) {
+                let label = edge.weight().node_label().map(|label| {
+                    let hash = stable_hash(&label);
+                    let id = edge_dictionary.intern(label);
+                    label_hashes.edges.insert(id, hash);
+                    id
+                });
+                outgoing.push(
+                    position,
+                    positions[graph.to_index(edge.target())],
+                    label,
+                    1.0,
+                );
+            }
+        }
+        let mut normalised =
+            Self::from_adjacency(indices(graph, &nodes), outgoing, graph.is_directed());
+
+        let mut labels: Vec<_> = graph
+            .node_references()
+            .map(|node| {
+                (
+                    positions[graph.to_index(node.id())],
+                    node.weight().node_label(),
+                )
+            })
+            .collect();
+        labels.sort_unstable_by_key(|&(position, _)| position);
+
+        let mut label_values = HashMap::new();
+        let mut intern = |label: <G::NodeWeight as NodeLabel>::Label| {
+            let hash = stable_hash(&label);
+            let value = label.label_value();
+            let id = dictionary.intern(label);
+            label_hashes.nodes.insert(id, hash);
+            if let Some(value) = value {
+                label_values.insert(id, value);
+            }
+            InitialLabel::Label(id)
+        };
+        normalised.missing = 0;
+        for (position, label) in labels {
+            normalised.labels[position] = match (label, missing) {
+                (Some(label), _) => intern(label),
+                (None, strategy) => {
+                    normalised.missing += 1;
+                    match strategy {
+                        MissingLabelStrategy::Constant(label) => intern(label.clone()),
+                        MissingLabelStrategy::Degree => {
+                            InitialLabel::Degree(normalised.degree(position))
+                        }
+                        MissingLabelStrategy::Error | MissingLabelStrategy::Unknown => {
+                            InitialLabel::Unknown
+                        }
+                    }
+                }
+            };
+        }
+        normalised.label_hashes = label_hashes;
+        normalised.label_values = label_values;
+        Ok(normalised)
+    }
+
+    /// Completes the outgoing edge lists into an unlabeled graph
+    fn from_adjacency(indices: Vec<usize>, outgoing: Adjacency, directed: bool) -> Self {
+        let node_count = outgoing.neighbors.len();
+        let incoming = if directed {
+            outgoing.reversed()
+        } else {
+            Adjacency::with_nodes(node_count)
+        };
+        let mut graph = WlGraph {
+            labels: Vec::new(),
+            indices,
+            outgoing,
+            incoming,
+            directed,
+            missing: node_count,
+            label_hashes: LabelHashes::default(),
+            label_values: HashMap::new(),
+        };
+        graph.labels = (0..node_count)
+            .map(|node| InitialLabel::Degree(graph.degree(node)))
+            .collect();
+        graph
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.outgoing.neighbors.len()
+    }
+
+    /// Petgraph index of `node`, as reported by `NodeIndexable::to_index`
+    ///
+    /// For `Graph` and `StableGraph` this is the `NodeIndex` of the node.
+    pub fn node_index(&self, node: usize) -> usize {
+        self.indices[node]
+    }
+
+    /// Whether the graph came from a directed container
+    pub fn is_directed(&self) -> bool {
+        self.directed
+    }
+
+    /// Initial node labels in normalised node order
+    pub fn labels(&self) -> &[InitialLabel] {
+        &self.labels
+    }
+
+    /// Whether at least one node carries a label of its own
+    pub fn is_labeled(&self) -> bool {
+        self.missing < self.node_count()
+    }
+
+    /// Number of nodes whose label was filled in by a [`MissingLabelStrategy`]
+    pub fn missing_labels(&self) -> usize {
+        self.missing
+    }
+
+    /// Neighbours of `node`, one entry per incident edge, or per outgoing
+    /// edge in a directed graph
+    pub fn neighbors(&self, node: usize) -> &[usize] {
+        &self.outgoing.neighbors[node]
+    }
+
+    /// Interned labels of the edges of `node`, aligned with
+    /// [`WlGraph::neighbors`]; `None` for unlabeled edges
+    pub fn edge_labels(&self, node: usize) -> &[Option<usize>] {
+        &self.outgoing.labels[node]
+    }
+
+    /// Strengths of the edges of `node`, aligned with [`WlGraph::neighbors`]
+    pub fn edge_weights(&self, node: usize) -> &[f64] {
+        &self.outgoing.weights[node]
+    }
+
+    /// Sources of the incoming edges of `node`; empty for undirected graphs
+    pub fn in_neighbors(&self, node: usize) -> &[usize] {
+        &self.incoming.neighbors[node]
+    }
+
+    /// Labels of the incoming edges of `node`, aligned with
+    /// [`WlGraph::in_neighbors`]
+    pub fn in_edge_labels(&self, node: usize) -> &[Option<usize>] {
+        &self.incoming.labels[node]
+    }
+
+    /// Strengths of the incoming edges of `node`, aligned with
+    /// [`WlGraph::in_neighbors`]
+    pub fn in_edge_weights(&self, node: usize) -> &[f64] {
+        &self.incoming.weights[node]
+    }
+
+    /// Whether at least one edge has a strength other than one
+    pub fn is_weighted(&self) -> bool {
+        self.outgoing
+            .weights
+            .iter()
+            .flatten()
+            .any(|&weight| weight != 1.0)
+    }
+
+    /// Whether at least one edge carries a label
+    pub fn has_edge_labels(&self) -> bool {
+        self.outgoing.labels.iter().flatten().any(Option::is_some)
+    }
+
+    /// Number of edge entries of `node`, counting both directions in a
+    /// directed graph
+    pub fn degree(&self, node: usize) -> usize {
+        self.outgoing.neighbors[node].len() + self.incoming.neighbors[node].len()
+    }
+
+    /// Initial labels as scalar features, as read under
+    /// [`DistanceConfig::enforce_continuous`](crate::DistanceConfig::enforce_continuous)
+    ///
+    /// Labels contribute their [`LabelValue`] and degree stand-ins the degree
+    /// itself, as in the Python `wwl` package. Fails with the first node whose
+    /// label has no numeric value.
+    #[cfg(any(feature = "native", feature = "python", feature = "worker"))]
+    pub(crate) fn label_features(&self) -> Result<Vec<f64>, usize> {
+        self.labels
+            .iter()
+            .enumerate()
+            .map(|(node, label)| match label {
+                InitialLabel::Label(id) => self.label_values.get(id).copied().ok_or(node),
+                InitialLabel::Degree(degree) => Ok(*degree as f64),
+                InitialLabel::Unknown => Err(node),
+            })
+            .collect()
+    }
+
+    /// Hash identifying the initial label of `node` in any collection
+    ///
+    /// Interned ids depend on the collection a graph was interned with; the
+    /// hash is derived from the label itself instead. Fails for an id whose
+    /// label was not recorded by [`WlGraph::from_labeled`].
+    #[cfg(feature = "native")]
+    pub(crate) fn node_label_hash(&self, node: usize) -> Result<u64, WwlError> {
+        let mut hasher = StableHasher::default();
+        match self.labels[node] {
+            InitialLabel::Label(id) => {
+                hasher.write_u8(0);
+                hasher.write_u64(self.recorded_hash(&self.label_hashes.nodes, id)?);
+            }
+            InitialLabel::Degree(degree) => {
+                hasher.write_u8(1);
+                hasher.write_u64(degree as u64);
+            }
+            InitialLabel::Unknown => hasher.write_u8(2),
+        }
+        Ok(hasher.finish())
+    }
+
+    /// Hash identifying an edge label in any collection, see
+    /// [`WlGraph::node_label_hash`]
+    #[cfg(feature = "native")]
+    pub(crate) fn edge_label_hash(&self, label: Option<usize>) -> Result<Option<u64>, WwlError> {
+        label
+            .map(|id| self.recorded_hash(&self.label_hashes.edges, id))
+            .transpose()
+    }
+
+    #[cfg(feature = "native")]
+    fn recorded_hash(&self, hashes: &HashMap<usize, u64>, id: usize) -> Result<u64, WwlError> {
+        hashes.get(&id).copied().ok_or_else(|| {
+            WwlError::Cache(format!(
+                "label id {} has no recorded label; build graphs with intern_graphs",
+                id
+            ))
+        })
+    }
+}
+
+/// FNV-1a hasher whose output depends neither on the process nor on the
+/// platform, for hashes that outlive a run
+///
+/// Integers are fed as little-endian bytes of fixed width, so labels of the
+/// built-in [`NodeLabel`] types hash alike on every platform.
+#[derive(Clone, Debug)]
+pub(crate) struct StableHasher(u64);
+
+impl Default for StableHasher {
+    fn default() -> Self {
+        StableHasher(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for StableHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn write_u16(&mut self, value: u16) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_u32(&mut self, value: u32) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_u128(&mut self, value: u128) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_usize(&mut self, value: usize) {
+        self.write_u64(value as u64);
+    }
+}
+
+/// Hash of `value` with [`StableHasher`]
+pub(crate) fn stable_hash<T: Hash + ?Sized>(value: &T) -> u64 {
+    let mut hasher = StableHasher::default();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Petgraph indices of `nodes`
+fn indices<G: NodeIndexable>(graph: G, nodes: &[G::NodeId]) -> Vec<usize> {
+    nodes.iter().map(|&node| graph.to_index(node)).collect()
+}
+
+/// Lists the nodes in petgraph index order, together with a map from
+/// petgraph index to position in that order
+fn ordered_nodes<G>(graph: G) -> (Vec<G::NodeId>, Vec<usize>)
+where
+    G: IntoNodeIdentifiers + NodeIndexable + NodeCount,
+{
+    let mut nodes = Vec::with_capacity(graph.node_count());
+    nodes.extend(graph.node_identifiers());
+    nodes.sort_unstable_by_key(|&node| graph.to_index(node));
+
+    let mut positions = vec![usize::MAX; graph.node_bound()];
+    for (position, &node) in nodes.iter().enumerate() {
+        positions[graph.to_index(node)] = position;
+    }
+    (nodes, positions)
+}
+
+/// Petgraph container accepted by the continuous entry points
+///
+/// Implemented for every `G` whose references implement `IntoNeighbors`,
+/// `IntoNodeIdentifiers`, `NodeIndexable`, `NodeCount` and `GraphProp`, which
+/// covers directed and undirected `Graph`, `StableGraph`, `GraphMap` and
+/// `Csr`.
+pub trait GraphInput {
+    fn to_wl_graph(&self) -> WlGraph;
+}
+
+impl<G> GraphInput for G
+where
+    for<'a> &'a G: IntoNodeIdentifiers + IntoNeighbors + NodeIndexable + NodeCount + GraphProp,
+{
+    fn to_wl_graph(&self) -> WlGraph {
+        WlGraph::from_topology(self)
+    }
+}
+
+/// Petgraph container whose edge weights carry strengths
+///
+/// Implemented for every [`GraphInput`] whose edge weight implements
+/// [`EdgeWeight`], including unweighted `()` edges.
+pub trait WeightedGraphInput: GraphInput {
+    fn to_weighted_wl_graph(&self) -> WlGraph;
+}
+
+impl<G, E> WeightedGraphInput for G
+where
+    for<'a> &'a G: IntoNodeIdentifiers
+        + IntoEdges
+        + NodeIndexable
+        + NodeCount
+        + GraphProp
+        + petgraph::visit::Data<EdgeWeight = E>,
+    E: EdgeWeight,
+{
+    fn to_weighted_wl_graph(&self) -> WlGraph {
+        WlGraph::from_weighted(self)
+    }
+}
+
+/// Petgraph container whose node weights carry categorical labels
+///
+/// Implemented for every [`GraphInput`] whose node and edge weights implement
+/// [`NodeLabel`]; edges of type `()` are unlabeled.
+pub trait LabeledGraphInput: GraphInput {
+    type Label: Hash + Eq + Clone + 'static;
+    type EdgeLabel: Hash + Eq + Clone + 'static;
+
+    fn to_labeled_wl_graph(
+        &self,
+        dictionary: &mut LabelDictionary<Self::Label>,
+        edge_dictionary: &mut LabelDictionary<Self::EdgeLabel>,
+        missing: &MissingLabelStrategy<Self::Label>,
+    ) -> Result<WlGraph, WwlError>;
+}
+
+impl<G, N, E> LabeledGraphInput for G
+where
+    for<'a> &'a G: IntoNodeReferences
+        + IntoEdges
+        + NodeIndexable
+        + NodeCount
+        + GraphProp
+        + petgraph::visit::Data<NodeWeight = N, EdgeWeight = E>,
+    N: NodeLabel,
+    E: NodeLabel,
+{
+    type Label = N::Label;
+    type EdgeLabel = E::Label;
+
+    fn to_labeled_wl_graph(
+        &self,
+        dictionary: &mut LabelDictionary<N::Label>,
+        edge_dictionary: &mut LabelDictionary<E::Label>,
+        missing: &MissingLabelStrategy<N::Label>,
+    ) -> Result<WlGraph, WwlError> {
+        WlGraph::from_labeled(self, dictionary, edge_dictionary, missing)
+    }
+}
+
+/// Node and edge label dictionaries of an interned collection
+pub type LabelDictionaries<G> = (
+    LabelDictionary<<G as LabeledGraphInput>::Label>,
+    LabelDictionary<<G as LabeledGraphInput>::EdgeLabel>,
+);
+
+/// Normalises a labeled collection, interning its node and edge labels into
+/// one dictionary each
+///
+/// Unlabeled nodes are resolved with `missing`; under
+/// [`MissingLabelStrategy::Error`] any unlabeled node fails the whole
+/// collection with [`WwlError::MissingLabels`].
+pub fn intern_graphs<G: LabeledGraphInput>(
+    graphs: &[G],
+    missing: &MissingLabelStrategy<G::Label>,
+) -> Result<(Vec<WlGraph>, LabelDictionaries<G>), WwlError> {
+    let mut dictionaries = (LabelDictionary::new(), LabelDictionary::new());
+    let graphs = intern_graphs_with(graphs, &mut dictionaries, missing)?;
+    Ok((graphs, dictionaries))
+}
+
+/// Like [`intern_graphs`], but extends existing dictionaries so that labels
+/// seen before keep their ids
+pub fn intern_graphs_with<G: LabeledGraphInput>(
+    graphs: &[G],
+    dictionaries: &mut LabelDictionaries<G>,
+    missing: &MissingLabelStrategy<G::Label>,
+) -> Result<Vec<WlGraph>, WwlError> {
+    let (dictionary, edge_dictionary) = dictionaries;
+    let graphs = graphs
+        .iter()
+        .map(|graph| graph.to_labeled_wl_graph(dictionary, edge_dictionary, missing))
+        .collect::<Result<Vec<_>, _>>()?;
+    reject_missing_labels(&graphs, missing)?;
+    Ok(graphs)
+}
+
+/// Fails with [`WwlError::MissingLabels`] under
+/// [`MissingLabelStrategy::Error`] if any graph has unlabeled nodes
+pub(crate) fn reject_missing_labels<L>(
+    graphs: &[WlGraph],
+    missing: &MissingLabelStrategy<L>,
+) -> Result<(), WwlError> {
+    if let MissingLabelStrategy::Error = missing {
+        let affected = graphs_with_missing_labels(graphs);
+        if !affected.is_empty() {
+            return Err(WwlError::MissingLabels { graphs: affected });
+        }
+    }
+    Ok(())
+}
+
+/// Indices of the graphs with at least one unlabeled node
+pub fn graphs_with_missing_labels(graphs: &[WlGraph]) -> Vec<usize> {
+    graphs
+        .iter()
+        .enumerate()
+        .filter(|(_, graph)| graph.missing_labels() > 0)
+        .map(|(index, _)| index)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GraphType;
+    use petgraph::csr::Csr;
+    use petgraph::graphmap::UnGraphMap;
+    use petgraph::stable_graph::StableUnGraph;
+    use petgraph::Graph;
+
+    #[cfg(feature = "native")]
+    #[test]
+    fn test_unrecorded_label_hash_is_an_error() {
+        let mut graph: GraphType = Graph::new_undirected();
+        let a = graph.add_node(Some(1));
+        let b = graph.add_node(Some(2));
+        graph.add_edge(a, b, ());
+        let (mut graphs, _) = intern_graphs(&[graph], &MissingLabelStrategy::Error).unwrap();
+        assert!(graphs[0].node_label_hash(1).is_ok());
+        assert_eq!(graphs[0].edge_label_hash(None).unwrap(), None);
+
+        graphs[0].label_hashes = LabelHashes::default();
+        assert!(matches!(
+            graphs[0].node_label_hash(1),
+            Err(WwlError::Cache(_))
+        ));
+        assert!(matches!(
+            graphs[0].edge_label_hash(Some(0)),
+            Err(WwlError::Cache(_))
+        ));
+    }
+
+    #[test]
+    fn test_graph_type_normalisation() {
+        let mut graph: GraphType = Graph::new_undirected();
+        let a = graph.add_node(Some(1));
+        let b = graph.add_node(None);
+        let c = graph.add_node(Some(3));
+        graph.add_edge(a, b, ());
+        graph.add_edge(b, c, ());
+        graph.add_edge(c, c, ());
+
+        let mut dictionary = LabelDictionary::new();
+        let normalised = graph
+            .to_labeled_wl_graph(
+                &mut dictionary,
+                &mut LabelDictionary::new(),
+                &MissingLabelStrategy::Unknown,
+            )
+            .unwrap();
+        assert_eq!(
+            normalised.labels(),
+            &[
+                InitialLabel::Label(0),
+                InitialLabel::Unknown,
+                InitialLabel::Label(1)
+            ]
+        );
+        assert_eq!(normalised.missing_labels(), 1);
+        assert_eq!(dictionary.labels(), &[1, 3]);
+        assert_eq!(normalised.neighbors(0), &[1]);
+        assert_eq!(normalised.degree(1), 2);
+        // Self loops are reported once
+        assert_eq!(normalised.degree(2), 2);
+    }
+
+    #[test]
+    fn test_stable_graph_skips_removed_nodes() {
+        let mut graph = StableUnGraph::<i32, ()>::default();
+        let a = graph.add_node(10);
+        let b = graph.add_node(20);
+        let c = graph.add_node(30);
+        graph.add_edge(a, c, ());
+        graph.remove_node(b);
+
+        let mut dictionary = LabelDictionary::new();
+        let normalised = graph
+            .to_labeled_wl_graph(
+                &mut dictionary,
+                &mut LabelDictionary::new(),
+                &MissingLabelStrategy::Error,
+            )
+            .unwrap();
+        assert_eq!(normalised.node_count(), 2);
+        assert_eq!(
+            normalised.labels(),
+            &[InitialLabel::Label(0), InitialLabel::Label(1)]
+        );
+        assert_eq!(dictionary.labels(), &[10, 30]);
+        assert_eq!(normalised.neighbors(0), &[1]);
+        assert_eq!(normalised.neighbors(1), &[0]);
+    }
+
+    #[test]
+    fn test_graph_map_and_csr() {
+        let mut map = UnGraphMap::<i32, ()>::new();
+        map.add_edge(7, 8, ());
+        map.add_edge(8, 9, ());
+        let mut dictionary = LabelDictionary::new();
+        let normalised = map
+            .to_labeled_wl_graph(
+                &mut dictionary,
+                &mut LabelDictionary::new(),
+                &MissingLabelStrategy::default(),
+            )
+            .unwrap();
+        assert_eq!(normalised.labels()[2], InitialLabel::Label(2));
+        assert_eq!(normalised.degree(1), 2);
+
+        let csr: Csr<(), ()> = Csr::from_sorted_edges(&[(0, 1), (1, 2)]).unwrap();
+        let normalised = csr.to_wl_graph();
+        assert_eq!(normalised.node_count(), 3);
+        assert!(!normalised.is_labeled());
+        // `Csr` is directed by default: one edge in, one edge out
+        assert!(normalised.is_directed());
+        assert_eq!(normalised.labels()[1], InitialLabel::Degree(2));
+        assert_eq!(normalised.neighbors(1), &[2]);
+        assert_eq!(normalised.in_neighbors(1), &[0]);
+    }
+
+    #[test]
+    fn test_labels_interned_across_collection() {
+        let mut first = Graph::new_undirected();
+        let c = first.add_node("C");
+        let o = first.add_node("O");
+        first.add_edge(c, o, ());
+
+        let mut second = Graph::new_undirected();
+        let o = second.add_node("O");
+        let n = second.add_node("N");
+        second.add_edge(o, n, ());
+
+        let (graphs, (dictionary, _)) =
+            intern_graphs(&[first, second], &MissingLabelStrategy::Error).unwrap();
+        assert_eq!(
+            graphs[0].labels(),
+            &[InitialLabel::Label(0), InitialLabel::Label(1)]
+        );
+        assert_eq!(
+            graphs[1].labels(),
+            &[InitialLabel::Label(1), InitialLabel::Label(2)]
+        );
+        assert_eq!(dictionary.label(1), Some(&"O"));
+        assert_eq!(dictionary.id(&"N"), Some(2));
+    }
+
+    #[test]
+    fn test_missing_label_strategies() {
+        let mut labeled: GraphType = Graph::new_undirected();
+        let a = labeled.add_node(Some(5));
+        let b = labeled.add_node(Some(6));
+        labeled.add_edge(a, b, ());
+
+        let mut partial: GraphType = Graph::new_undirected();
+        let a = partial.add_node(Some(5));
+        let b = partial.add_node(None);
+        let c = partial.add_node(None);
+        partial.add_edge(a, b, ());
+        partial.add_edge(b, c, ());
+
+        let mut unlabeled: GraphType = Graph::new_undirected();
+        unlabeled.add_node(None);
+
+        let collection = [labeled, partial, unlabeled];
+
+        let err = intern_graphs(&collection, &MissingLabelStrategy::Error).unwrap_err();
+        assert_eq!(err, WwlError::MissingLabels { graphs: vec![1, 2] });
+
+        let (graphs, _) = intern_graphs(&collection, &MissingLabelStrategy::Degree).unwrap();
+        assert_eq!(
+            graphs[1].labels(),
+            &[
+                InitialLabel::Label(0),
+                InitialLabel::Degree(2),
+                InitialLabel::Degree(1)
+            ]
+        );
+        assert_eq!(graphs_with_missing_labels(&graphs), vec![1, 2]);
+
+        let (graphs, (dictionary, _)) =
+            intern_graphs(&collection, &MissingLabelStrategy::Constant(6)).unwrap();
+        assert_eq!(graphs[2].labels(), &[InitialLabel::Label(1)]);
+        assert_eq!(dictionary.labels(), &[5, 6]);
+    }
+
+    #[test]
+    fn test_edge_labels_follow_neighbors() {
+        let mut graph = Graph::new_undirected();
+        let c = graph.add_node("C");
+        let o = graph.add_node("O");
+        let n = graph.add_node("N");
+        graph.add_edge(c, o, 2u8);
+        graph.add_edge(n, c, 1u8);
+        graph.add_edge(n, n, 1u8);
+
+        let (graphs, (_, edge_dictionary)) =
+            intern_graphs(&[graph], &MissingLabelStrategy::Error).unwrap();
+        let normalised = &graphs[0];
+        assert!(normalised.has_edge_labels());
+        let single = edge_dictionary.id(&1);
+        let double = edge_dictionary.id(&2);
+        assert_eq!(edge_dictionary.len(), 2);
+
+        for node in 0..normalised.node_count() {
+            assert_eq!(
+                normalised.neighbors(node).len(),
+                normalised.edge_labels(node).len()
+            );
+        }
+        let bond = |a: usize, b: usize| {
+            let index = normalised.neighbors(a).iter().position(|&x| x == b);
+            normalised.edge_labels(a)[index.unwrap()]
+        };
+        assert_eq!(bond(0, 1), double);
+        assert_eq!(bond(1, 0), double);
+        assert_eq!(bond(0, 2), single);
+        assert_eq!(bond(2, 2), single);
+        // Self loops are reported once, like unlabeled ones
+        assert_eq!(normalised.degree(2), 2);
+
+        let unlabeled: GraphType = Graph::new_undirected();
+        assert!(!unlabeled.to_wl_graph().has_edge_labels());
+    }
+
+    #[test]
+    fn test_edge_weights_follow_neighbors() {
+        let mut graph = Graph::<(), f64, _>::new_undirected();
+        let a = graph.add_node(());
+        let b = graph.add_node(());
+        let c = graph.add_node(());
+        graph.add_edge(a, b, 0.5);
+        graph.add_edge(c, a, 2.0);
+
+        let normalised = graph.to_weighted_wl_graph();
+        assert!(normalised.is_weighted());
+        for (&neighbor, &weight) in normalised
+            .neighbors(0)
+            .iter()
+            .zip(normalised.edge_weights(0))
+        {
+            assert_eq!(weight, if neighbor == 1 { 0.5 } else { 2.0 });
+        }
+        assert_eq!(normalised.edge_weights(2), &[2.0]);
+
+        let unweighted: GraphType = Graph::new_undirected();
+        assert!(!unweighted.to_weighted_wl_graph().is_weighted());
+    }
+}
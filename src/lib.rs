@@ -1,87 +1,301 @@
 //! WWL (Wasserstein Weisfeiler-Lehman) Rust Bindings
 //!
-//! This crate provides Rust bindings for the WWL Graph Kernels Python library.
+//! This crate computes Wasserstein Weisfeiler-Lehman graph kernels, either
+//! through the WWL Graph Kernels Python library or with a pure Rust engine.
 //!
 //! The library correctly handles graphs of different sizes for categorical
 //! propagation (labeled graphs) and supports continuous propagation with
 //! proper node features.
 //!
+//! ## Backends
+//!
+//! [`WWLKernel`] delegates to a [`Backend`]. The `python` feature compiles
+//! [`PythonBackend`], which embeds CPython and calls the `wwl` module; the
+//! `native` feature compiles [`NativeBackend`], which needs no Python runtime.
+//! The `worker` feature compiles `WorkerBackend`, which runs the `wwl` module
+//! in separate Python processes instead of an embedded interpreter. All three
+//! features are enabled by default.
+//!
+//! ## Fitted Models
+//!
+//! [`WwlModel`] is fitted on training graphs and keeps their WL label space,
+//! so that test graphs embedded later are comparable with the training
+//! embeddings.
+//!
 //! ## Node Features
 //!
 //! Node features are numerical vectors associated with each node in a graph.
 //! They enable continuous propagation schemes where the algorithm operates on
 //! real-valued node attributes instead of discrete labels.
 
-use petgraph::{Graph, Undirected};
+use petgraph::{Directed, Graph, Undirected};
 
 use ndarray::Array2;
-use numpy::{PyArray2, PyArrayMethods};
-use pyo3::prelude::*;
-use pyo3::types::{PyAny, PyDict, PyList};
-use pythonize::pythonize;
+
+pub mod backend;
+#[cfg(feature = "native")]
+pub mod cache;
+pub mod dataset;
+mod error;
+pub mod graph;
+pub mod model;
+pub mod propagation;
+pub mod transport;
+
+pub use backend::Backend;
+#[cfg(feature = "native")]
+pub use backend::NativeBackend;
+#[cfg(feature = "python")]
+pub use backend::PythonBackend;
+use backend::{kernel_distance_config, laplacian_kernel, pair_kernel};
+#[cfg(any(feature = "python", feature = "worker"))]
+pub use backend::{Diagnostics, Package, PackageStatus, PythonEnvironment};
+#[cfg(feature = "worker")]
+pub use backend::{WorkerBackend, WorkerConfig};
+#[cfg(feature = "native")]
+pub use cache::{CacheStats, EmbeddingCache};
+pub use dataset::TuDataset;
+pub use error::WwlError;
+pub use graph::{
+    graphs_with_missing_labels, intern_graphs, intern_graphs_with, EdgeWeight, GraphInput,
+    InitialLabel, LabelDictionaries, LabelDictionary, LabelValue, LabeledGraphInput,
+    MissingLabelStrategy, NodeLabel, WeightedGraphInput, WlGraph,
+};
+pub use model::WwlModel;
+use propagation::{continuous_propagation, WlCompression};
+pub use transport::{Alignment, Coupling, SinkhornConfig};
 
 /// Graph type alias for undirected graphs with optional integer node weights
 pub type GraphType = Graph<Option<i32>, (), Undirected>;
 
+/// Directed counterpart of [`GraphType`]
+pub type DiGraphType = Graph<Option<i32>, (), Directed>;
+
 /// WWL Kernel implementation
+///
+/// `WWLKernel` is `Send + Sync`: one instance can be shared between threads,
+/// for example behind an `Arc`, and used concurrently. The native backend
+/// runs concurrent calls independently. The Python backend prepares graphs
+/// outside the interpreter lock, but calls into the Python library still take
+/// turns on the interpreter.
 pub struct WWLKernel {
-    wwl: Py<PyAny>,
+    backend: Box<dyn Backend>,
 }
 
 /// Configuration for WWL kernel computation
+///
+/// `L` is the node label type of labeled graphs, see
+/// [`MissingLabelStrategy`]; it is irrelevant for graphs with node features.
 #[derive(Clone)]
-pub struct KernelConfig {
+pub struct KernelConfig<L = i32> {
     pub num_iterations: usize,
     pub sinkhorn: bool,
+    /// Solver parameters used by the native backend when `sinkhorn` is set
+    pub sinkhorn_config: SinkhornConfig,
     pub gamma: Option<f64>,
+    /// Treatment of unlabeled nodes in categorical propagation
+    pub missing_labels: MissingLabelStrategy<L>,
+    /// Worker threads of the native backend, all available cores if `None`
+    pub num_threads: Option<usize>,
 }
 
-impl Default for KernelConfig {
+impl<L> Default for KernelConfig<L> {
     fn default() -> Self {
         Self {
             num_iterations: 3,
             sinkhorn: false,
+            sinkhorn_config: SinkhornConfig::default(),
             gamma: None,
+            missing_labels: MissingLabelStrategy::default(),
+            num_threads: None,
         }
     }
 }
 
 /// Configuration for Wasserstein distance computation
+///
+/// `L` is the node label type of labeled graphs, as for [`KernelConfig`].
 #[derive(Clone)]
-pub struct DistanceConfig {
+pub struct DistanceConfig<L = i32> {
     pub num_iterations: usize,
     pub sinkhorn: bool,
+    /// Solver parameters used by the native backend when `sinkhorn` is set
+    pub sinkhorn_config: SinkhornConfig,
     pub enforce_continuous: bool,
+    /// Treatment of unlabeled nodes in categorical propagation
+    pub missing_labels: MissingLabelStrategy<L>,
+    /// Worker threads of the native backend, all available cores if `None`
+    ///
+    /// The Python backend ignores this setting.
+    pub num_threads: Option<usize>,
 }
 
-impl Default for DistanceConfig {
+impl<L> Default for DistanceConfig<L> {
     fn default() -> Self {
         Self {
             num_iterations: 3,
             sinkhorn: false,
+            sinkhorn_config: SinkhornConfig::default(),
             enforce_continuous: false,
+            missing_labels: MissingLabelStrategy::default(),
+            num_threads: None,
+        }
+    }
+}
+
+impl<L> KernelConfig<L> {
+    /// Checks the configuration before any computation is started
+    pub fn validate(&self) -> Result<(), WwlError> {
+        validate_gamma(self.gamma)?;
+        validate_num_threads(self.num_threads)?;
+        self.sinkhorn_config.validate()
+    }
+
+    /// The configuration handed to backends, without the label type
+    pub(crate) fn erase_label(&self) -> KernelConfig<()> {
+        KernelConfig {
+            num_iterations: self.num_iterations,
+            sinkhorn: self.sinkhorn,
+            sinkhorn_config: self.sinkhorn_config.clone(),
+            gamma: self.gamma,
+            missing_labels: self.missing_labels.erase_label(),
+            num_threads: self.num_threads,
+        }
+    }
+}
+
+impl<L> DistanceConfig<L> {
+    /// Checks the configuration before any computation is started
+    pub fn validate(&self) -> Result<(), WwlError> {
+        validate_num_threads(self.num_threads)?;
+        self.sinkhorn_config.validate()
+    }
+
+    /// The configuration handed to backends, without the label type
+    pub(crate) fn erase_label(&self) -> DistanceConfig<()> {
+        DistanceConfig {
+            num_iterations: self.num_iterations,
+            sinkhorn: self.sinkhorn,
+            sinkhorn_config: self.sinkhorn_config.clone(),
+            enforce_continuous: self.enforce_continuous,
+            missing_labels: self.missing_labels.erase_label(),
+            num_threads: self.num_threads,
+        }
+    }
+}
+
+pub(crate) fn validate_gamma(gamma: Option<f64>) -> Result<(), WwlError> {
+    if let Some(gamma) = gamma {
+        if !(gamma.is_finite() && gamma > 0.0) {
+            return Err(WwlError::InvalidConfig(format!(
+                "gamma must be positive and finite, got {}",
+                gamma
+            )));
         }
     }
+    Ok(())
+}
+
+fn validate_num_threads(num_threads: Option<usize>) -> Result<(), WwlError> {
+    if num_threads == Some(0) {
+        return Err(WwlError::InvalidConfig(
+            "num_threads must be at least 1".to_string(),
+        ));
+    }
+    Ok(())
 }
 
 impl WWLKernel {
     /// Creates a new WWLKernel instance
-    pub fn new() -> Result<Self, String> {
-        Python::attach(|py| match py.import("wwl") {
-            Ok(wwl) => Ok(WWLKernel {
-                wwl: wwl.unbind().into(),
-            }),
-            Err(e) => Err(format!("Failed to import WWL module: {}", e)),
-        })
+    ///
+    /// Uses the Python backend when the `python` feature is enabled.
+    #[cfg(feature = "python")]
+    pub fn new() -> Result<Self, WwlError> {
+        Self::python()
+    }
+
+    /// Creates a new WWLKernel instance
+    ///
+    /// Uses the native backend since the `python` feature is disabled.
+    #[cfg(all(feature = "native", not(feature = "python")))]
+    pub fn new() -> Result<Self, WwlError> {
+        Ok(Self::native())
+    }
+
+    /// Creates a new WWLKernel instance
+    ///
+    /// Uses the worker backend since only the `worker` feature is enabled.
+    #[cfg(all(feature = "worker", not(any(feature = "python", feature = "native"))))]
+    pub fn new() -> Result<Self, WwlError> {
+        Self::worker()
+    }
+
+    /// Creates a WWLKernel backed by the Python WWL library
+    #[cfg(feature = "python")]
+    pub fn python() -> Result<Self, WwlError> {
+        Ok(Self::with_backend(PythonBackend::new()?))
+    }
+
+    /// Creates a WWLKernel backed by the Python WWL library of a virtualenv
+    ///
+    /// See [`PythonBackend::with_environment`].
+    #[cfg(feature = "python")]
+    pub fn python_with_environment(environment: &PythonEnvironment) -> Result<Self, WwlError> {
+        Ok(Self::with_backend(PythonBackend::with_environment(
+            environment,
+        )?))
+    }
+
+    /// Creates a WWLKernel backed by the pure Rust engine
+    #[cfg(feature = "native")]
+    pub fn native() -> Self {
+        Self::with_backend(NativeBackend::new())
+    }
+
+    /// Creates a WWLKernel backed by a pool of Python worker processes
+    ///
+    /// Uses the default [`WorkerConfig`]; see [`WorkerBackend::with_config`]
+    /// to choose the interpreter and pool size.
+    #[cfg(feature = "worker")]
+    pub fn worker() -> Result<Self, WwlError> {
+        Ok(Self::with_backend(WorkerBackend::new()?))
+    }
+
+    /// Creates a WWLKernel backed by worker processes running the given
+    /// Python environment
+    #[cfg(feature = "worker")]
+    pub fn worker_with_environment(environment: &PythonEnvironment) -> Result<Self, WwlError> {
+        Ok(Self::with_backend(WorkerBackend::with_config(
+            WorkerConfig {
+                environment: environment.clone(),
+                ..Default::default()
+            },
+        )?))
+    }
+
+    /// Creates a WWLKernel running on the given backend
+    pub fn with_backend(backend: impl Backend + 'static) -> Self {
+        WWLKernel {
+            backend: Box::new(backend),
+        }
     }
 
     /// Computes the WWL kernel matrix for labeled graphs (categorical propagation)
-    pub fn compute_kernel_categorical(
+    ///
+    /// Accepts any petgraph container whose node weights implement
+    /// [`NodeLabel`], such as [`GraphType`]. Edge weights implementing
+    /// [`NodeLabel`] key each neighbour by the connecting edge label. Labels
+    /// are interned across the whole collection; [`intern_graphs`] reproduces
+    /// the mapping. Unlabeled nodes are resolved with `config.missing_labels`.
+    pub fn compute_kernel_categorical<G: LabeledGraphInput>(
         &self,
-        graphs: &[GraphType],
-        config: &KernelConfig,
-    ) -> Result<Array2<f64>, String> {
-        self.compute_kernel_impl(graphs, None, config)
+        graphs: &[G],
+        config: &KernelConfig<G::Label>,
+    ) -> Result<Array2<f64>, WwlError> {
+        config.validate()?;
+        let (graphs, _) = intern_graphs(graphs, &config.missing_labels)?;
+        self.backend
+            .compute_kernel(&graphs, None, &config.erase_label())
     }
 
     /// Computes the WWL kernel matrix with node features (continuous propagation)
@@ -98,141 +312,519 @@ impl WWLKernel {
     /// [[graph1_node1_feat, graph1_node2_feat, graph1_node3_feat],
     ///  [graph2_node1_feat, graph2_node2_feat, graph2_node3_feat]]
     /// ```
-    pub fn compute_kernel_continuous(
+    pub fn compute_kernel_continuous<G: GraphInput>(
         &self,
-        graphs: &[GraphType],
+        graphs: &[G],
         node_features: &Array2<f64>,
         config: &KernelConfig,
-    ) -> Result<Array2<f64>, String> {
-        self.validate_node_features(graphs, node_features)?;
-        self.compute_kernel_impl(graphs, Some(node_features), config)
+    ) -> Result<Array2<f64>, WwlError> {
+        let graphs = topologies(graphs);
+        self.validate_node_features(&graphs, node_features)?;
+        let node_features = split_node_features(&graphs, node_features);
+        self.attributed_kernel_matrix(&graphs, &node_features, config)
+    }
+
+    /// Computes the WWL kernel matrix with multi-dimensional node features
+    ///
+    /// `node_features[i]` is a `node_count × dims` matrix for graph `i`, with
+    /// rows in node index order. All graphs must share the same `dims`, and
+    /// features must be finite.
+    pub fn compute_kernel_attributed<G: GraphInput>(
+        &self,
+        graphs: &[G],
+        node_features: &[Array2<f64>],
+        config: &KernelConfig,
+    ) -> Result<Array2<f64>, WwlError> {
+        self.attributed_kernel_matrix(&topologies(graphs), node_features, config)
+    }
+
+    /// Computes the WWL kernel matrix with node features on weighted graphs
+    ///
+    /// Like [`WWLKernel::compute_kernel_attributed`], but neighbour features
+    /// are averaged with the edge weights as coefficients. Weights must be
+    /// finite and non-negative.
+    pub fn compute_kernel_weighted<G: WeightedGraphInput>(
+        &self,
+        graphs: &[G],
+        node_features: &[Array2<f64>],
+        config: &KernelConfig,
+    ) -> Result<Array2<f64>, WwlError> {
+        let graphs: Vec<WlGraph> = graphs.iter().map(G::to_weighted_wl_graph).collect();
+        self.attributed_kernel_matrix(&graphs, node_features, config)
     }
 
     /// Computes pairwise Wasserstein distances for labeled graphs
-    pub fn compute_distance_categorical(
+    pub fn compute_distance_categorical<G: LabeledGraphInput>(
         &self,
-        graphs: &[GraphType],
-        config: &DistanceConfig,
-    ) -> Result<Array2<f64>, String> {
-        self.compute_distance_impl(graphs, None, config)
+        graphs: &[G],
+        config: &DistanceConfig<G::Label>,
+    ) -> Result<Array2<f64>, WwlError> {
+        config.validate()?;
+        let (graphs, _) = intern_graphs(graphs, &config.missing_labels)?;
+        self.backend
+            .compute_distance(&graphs, None, &config.erase_label())
     }
 
     /// Computes pairwise Wasserstein distances with node features
-    pub fn compute_distance_continuous(
+    pub fn compute_distance_continuous<G: GraphInput>(
         &self,
-        graphs: &[GraphType],
+        graphs: &[G],
         node_features: &Array2<f64>,
         config: &DistanceConfig,
-    ) -> Result<Array2<f64>, String> {
-        self.validate_node_features(graphs, node_features)?;
-        self.compute_distance_impl(graphs, Some(node_features), config)
+    ) -> Result<Array2<f64>, WwlError> {
+        let graphs = topologies(graphs);
+        self.validate_node_features(&graphs, node_features)?;
+        let node_features = split_node_features(&graphs, node_features);
+        self.attributed_distance_matrix(&graphs, &node_features, config)
+    }
+
+    /// Computes pairwise Wasserstein distances with multi-dimensional node features
+    ///
+    /// See [`WWLKernel::compute_kernel_attributed`] for the feature layout.
+    pub fn compute_distance_attributed<G: GraphInput>(
+        &self,
+        graphs: &[G],
+        node_features: &[Array2<f64>],
+        config: &DistanceConfig,
+    ) -> Result<Array2<f64>, WwlError> {
+        self.attributed_distance_matrix(&topologies(graphs), node_features, config)
+    }
+
+    /// Computes pairwise Wasserstein distances with node features on weighted
+    /// graphs
+    ///
+    /// See [`WWLKernel::compute_kernel_weighted`] for the role of the weights.
+    pub fn compute_distance_weighted<G: WeightedGraphInput>(
+        &self,
+        graphs: &[G],
+        node_features: &[Array2<f64>],
+        config: &DistanceConfig,
+    ) -> Result<Array2<f64>, WwlError> {
+        let graphs: Vec<WlGraph> = graphs.iter().map(G::to_weighted_wl_graph).collect();
+        self.attributed_distance_matrix(&graphs, node_features, config)
+    }
+
+    /// Computes the WWL kernel matrix of labeled graphs for every number of
+    /// iterations
+    ///
+    /// Entry `h` equals [`WWLKernel::compute_kernel_categorical`] with `h`
+    /// iterations, for `h = 0..=config.num_iterations`. Backends that support
+    /// it, such as the native one, propagate only once, which makes selecting
+    /// `h` as cheap as a single run up to the transport problems.
+    pub fn compute_kernel_per_iteration_categorical<G: LabeledGraphInput>(
+        &self,
+        graphs: &[G],
+        config: &KernelConfig<G::Label>,
+    ) -> Result<Vec<Array2<f64>>, WwlError> {
+        config.validate()?;
+        let (graphs, _) = intern_graphs(graphs, &config.missing_labels)?;
+        let distances = self.backend.compute_distance_per_iteration(
+            &graphs,
+            None,
+            &kernel_distance_config(&config.erase_label()),
+        )?;
+        Ok(per_iteration_kernels(distances, config.gamma))
+    }
+
+    /// Computes the WWL kernel matrix with multi-dimensional node features for
+    /// every number of iterations
+    ///
+    /// See [`WWLKernel::compute_kernel_per_iteration_categorical`].
+    pub fn compute_kernel_per_iteration_attributed<G: GraphInput>(
+        &self,
+        graphs: &[G],
+        node_features: &[Array2<f64>],
+        config: &KernelConfig,
+    ) -> Result<Vec<Array2<f64>>, WwlError> {
+        config.validate()?;
+        let graphs = topologies(graphs);
+        self.validate_attributes(&graphs, node_features)?;
+        let distances = self.backend.compute_distance_per_iteration(
+            &graphs,
+            Some(node_features),
+            &kernel_distance_config(&config.erase_label()),
+        )?;
+        Ok(per_iteration_kernels(distances, config.gamma))
+    }
+
+    /// Computes pairwise Wasserstein distances of labeled graphs for every
+    /// number of iterations
+    ///
+    /// See [`WWLKernel::compute_kernel_per_iteration_categorical`].
+    pub fn compute_distance_per_iteration_categorical<G: LabeledGraphInput>(
+        &self,
+        graphs: &[G],
+        config: &DistanceConfig<G::Label>,
+    ) -> Result<Vec<Array2<f64>>, WwlError> {
+        config.validate()?;
+        let (graphs, _) = intern_graphs(graphs, &config.missing_labels)?;
+        self.backend
+            .compute_distance_per_iteration(&graphs, None, &config.erase_label())
+    }
+
+    /// Computes pairwise Wasserstein distances with multi-dimensional node
+    /// features for every number of iterations
+    ///
+    /// See [`WWLKernel::compute_kernel_per_iteration_categorical`].
+    pub fn compute_distance_per_iteration_attributed<G: GraphInput>(
+        &self,
+        graphs: &[G],
+        node_features: &[Array2<f64>],
+        config: &DistanceConfig,
+    ) -> Result<Vec<Array2<f64>>, WwlError> {
+        config.validate()?;
+        let graphs = topologies(graphs);
+        self.validate_attributes(&graphs, node_features)?;
+        self.backend.compute_distance_per_iteration(
+            &graphs,
+            Some(node_features),
+            &config.erase_label(),
+        )
+    }
+
+    /// Computes the WL node embeddings of labeled graphs
+    ///
+    /// Returns the label sequences of categorical propagation, one
+    /// `node_count × (num_iterations + 1)` matrix per graph, with ids shared
+    /// by the whole collection; see [`propagation::categorical_propagation`].
+    /// Embeddings are always computed natively, whatever the backend.
+    pub fn node_embeddings_categorical<G: LabeledGraphInput>(
+        &self,
+        graphs: &[G],
+        config: &DistanceConfig<G::Label>,
+    ) -> Result<Vec<Array2<usize>>, WwlError> {
+        config.validate()?;
+        let (graphs, _) = intern_graphs(graphs, &config.missing_labels)?;
+        Ok(WlCompression::fit(&graphs, config.num_iterations).1)
+    }
+
+    /// Computes the WL node embeddings of graphs with multi-dimensional node
+    /// features
+    ///
+    /// Returns the feature sequences of continuous propagation, one
+    /// `node_count × dims * (num_iterations + 1)` matrix per graph; see
+    /// [`propagation::continuous_propagation`]. Embeddings are always
+    /// computed natively, whatever the backend.
+    pub fn node_embeddings_attributed<G: GraphInput>(
+        &self,
+        graphs: &[G],
+        node_features: &[Array2<f64>],
+        config: &DistanceConfig,
+    ) -> Result<Vec<Array2<f64>>, WwlError> {
+        config.validate()?;
+        let graphs = topologies(graphs);
+        self.validate_attributes(&graphs, node_features)?;
+        Ok(continuous_propagation(
+            &graphs,
+            node_features,
+            config.num_iterations,
+        ))
+    }
+
+    /// Computes bag-of-labels histograms of labeled graphs
+    ///
+    /// Returns one row per graph and one column per WL label of the
+    /// collection at any iteration, counting the nodes that carry it; see
+    /// [`WlCompression::histograms`]. Use [`WwlModel::histograms`] to give
+    /// graphs seen later the same columns.
+    pub fn label_histograms_categorical<G: LabeledGraphInput>(
+        &self,
+        graphs: &[G],
+        config: &DistanceConfig<G::Label>,
+    ) -> Result<Array2<f64>, WwlError> {
+        config.validate()?;
+        let (graphs, _) = intern_graphs(graphs, &config.missing_labels)?;
+        let (compression, embeddings) = WlCompression::fit(&graphs, config.num_iterations);
+        Ok(compression.histograms(&embeddings))
+    }
+
+    /// Computes the Wasserstein distance between two labeled graphs
+    ///
+    /// Equals entry `[0, 1]` of [`WWLKernel::compute_distance_categorical`] on
+    /// the pair, without building the distance matrix, which suits tight
+    /// loops such as deduplication checks.
+    pub fn distance<G: LabeledGraphInput>(
+        &self,
+        first: &G,
+        second: &G,
+        config: &DistanceConfig<G::Label>,
+    ) -> Result<f64, WwlError> {
+        config.validate()?;
+        let graphs = intern_pair(first, second, &config.missing_labels)?;
+        self.backend
+            .compute_pair_distance(&graphs, None, &config.erase_label())
+    }
+
+    /// Computes the WWL kernel value of two labeled graphs
+    ///
+    /// Equals entry `[0, 1]` of [`WWLKernel::compute_kernel_categorical`] on
+    /// the pair alone for the same `config`: `exp(-2 * gamma * d)` for
+    /// distance `d`, with `gamma` defaulting to 1/2 as for any two graphs,
+    /// which makes the default the laplacian kernel `exp(-d)` of the WWL
+    /// paper. Matrices of larger collections compare graphs through their
+    /// rows of distances to the whole collection, so their entries differ.
+    pub fn kernel_value<G: LabeledGraphInput>(
+        &self,
+        first: &G,
+        second: &G,
+        config: &KernelConfig<G::Label>,
+    ) -> Result<f64, WwlError> {
+        config.validate()?;
+        let graphs = intern_pair(first, second, &config.missing_labels)?;
+        let distance = self.backend.compute_pair_distance(
+            &graphs,
+            None,
+            &kernel_distance_config(&config.erase_label()),
+        )?;
+        Ok(pair_kernel(distance, config.gamma))
+    }
+
+    /// Computes the Wasserstein distance between two graphs with
+    /// multi-dimensional node features
+    pub fn distance_attributed<G: GraphInput>(
+        &self,
+        first: (&G, &Array2<f64>),
+        second: (&G, &Array2<f64>),
+        config: &DistanceConfig,
+    ) -> Result<f64, WwlError> {
+        config.validate()?;
+        let (graphs, features) = attributed_pair(first, second);
+        self.validate_attributes(&graphs, &features)?;
+        self.backend
+            .compute_pair_distance(&graphs, Some(&features), &config.erase_label())
+    }
+
+    /// Computes the WWL kernel value of two graphs with multi-dimensional
+    /// node features
+    ///
+    /// Equals entry `[0, 1]` of [`WWLKernel::compute_kernel_attributed`] on
+    /// the pair alone, see [`WWLKernel::kernel_value`].
+    pub fn kernel_value_attributed<G: GraphInput>(
+        &self,
+        first: (&G, &Array2<f64>),
+        second: (&G, &Array2<f64>),
+        config: &KernelConfig,
+    ) -> Result<f64, WwlError> {
+        config.validate()?;
+        let (graphs, features) = attributed_pair(first, second);
+        self.validate_attributes(&graphs, &features)?;
+        let distance = self.backend.compute_pair_distance(
+            &graphs,
+            Some(&features),
+            &kernel_distance_config(&config.erase_label()),
+        )?;
+        Ok(pair_kernel(distance, config.gamma))
+    }
+
+    /// Computes the optimal transport plan between the nodes of two labeled
+    /// graphs
+    ///
+    /// The plan couples nodes whose WL embeddings are alike, showing which
+    /// substructures correspond between the graphs. Nodes are identified by
+    /// their petgraph index. Only backends that solve the transport problem
+    /// themselves, such as the native backend, can return plans.
+    pub fn transport_plan<G: LabeledGraphInput>(
+        &self,
+        first: &G,
+        second: &G,
+        config: &DistanceConfig<G::Label>,
+    ) -> Result<Alignment, WwlError> {
+        config.validate()?;
+        let graphs = intern_pair(first, second, &config.missing_labels)?;
+        let transport =
+            self.backend
+                .compute_pair_transport(&graphs, None, &config.erase_label())?;
+        Ok(transport.alignment(&graphs[0], &graphs[1]))
+    }
+
+    /// Computes the WWL kernel between test and training graphs
+    ///
+    /// Returns a `test.len() × train.len()` matrix, computing only the pairs it
+    /// needs. Labels of both collections are interned and compressed together.
+    /// A graph is represented by its distances to the training graphs, so
+    /// passing the training graphs as `test` reproduces
+    /// [`WWLKernel::compute_kernel_categorical`] on them. Indices reported by
+    /// [`WwlError::MissingLabels`] count training graphs first.
+    pub fn compute_cross_kernel_categorical<G: LabeledGraphInput>(
+        &self,
+        train: &[G],
+        test: &[G],
+        config: &KernelConfig<G::Label>,
+    ) -> Result<Array2<f64>, WwlError> {
+        config.validate()?;
+        let graphs = intern_collections(train, test, &config.missing_labels)?;
+        self.backend
+            .compute_cross_kernel(&graphs, train.len(), None, &config.erase_label())
+    }
+
+    /// Computes the WWL kernel between test and training graphs with
+    /// multi-dimensional node features
+    ///
+    /// See [`WWLKernel::compute_cross_kernel_categorical`] for the layout of
+    /// the result and [`WWLKernel::compute_kernel_attributed`] for the
+    /// features.
+    pub fn compute_cross_kernel_attributed<G: GraphInput>(
+        &self,
+        train: &[G],
+        train_features: &[Array2<f64>],
+        test: &[G],
+        test_features: &[Array2<f64>],
+        config: &KernelConfig,
+    ) -> Result<Array2<f64>, WwlError> {
+        config.validate()?;
+        let (graphs, features) =
+            attributed_collections(train, train_features, test, test_features)?;
+        self.validate_attributes(&graphs, &features)?;
+        self.backend.compute_cross_kernel(
+            &graphs,
+            train.len(),
+            Some(&features),
+            &config.erase_label(),
+        )
+    }
+
+    /// Computes Wasserstein distances from test graphs to training graphs
+    ///
+    /// Returns a `test.len() × train.len()` matrix, computing only the pairs it
+    /// needs. Labels of both collections are interned and compressed together.
+    /// Indices reported by [`WwlError::MissingLabels`] count training graphs
+    /// first.
+    pub fn compute_cross_distance_categorical<G: LabeledGraphInput>(
+        &self,
+        train: &[G],
+        test: &[G],
+        config: &DistanceConfig<G::Label>,
+    ) -> Result<Array2<f64>, WwlError> {
+        config.validate()?;
+        let graphs = intern_collections(train, test, &config.missing_labels)?;
+        self.backend
+            .compute_cross_distance(&graphs, train.len(), None, &config.erase_label())
+    }
+
+    /// Computes Wasserstein distances from test graphs to training graphs with
+    /// multi-dimensional node features
+    pub fn compute_cross_distance_attributed<G: GraphInput>(
+        &self,
+        train: &[G],
+        train_features: &[Array2<f64>],
+        test: &[G],
+        test_features: &[Array2<f64>],
+        config: &DistanceConfig,
+    ) -> Result<Array2<f64>, WwlError> {
+        config.validate()?;
+        let (graphs, features) =
+            attributed_collections(train, train_features, test, test_features)?;
+        self.validate_attributes(&graphs, &features)?;
+        self.backend.compute_cross_distance(
+            &graphs,
+            train.len(),
+            Some(&features),
+            &config.erase_label(),
+        )
+    }
+
+    fn attributed_kernel_matrix(
+        &self,
+        graphs: &[WlGraph],
+        node_features: &[Array2<f64>],
+        config: &KernelConfig,
+    ) -> Result<Array2<f64>, WwlError> {
+        config.validate()?;
+        self.validate_attributes(graphs, node_features)?;
+        self.backend
+            .compute_kernel(graphs, Some(node_features), &config.erase_label())
+    }
+
+    fn attributed_distance_matrix(
+        &self,
+        graphs: &[WlGraph],
+        node_features: &[Array2<f64>],
+        config: &DistanceConfig,
+    ) -> Result<Array2<f64>, WwlError> {
+        config.validate()?;
+        self.validate_attributes(graphs, node_features)?;
+        self.backend
+            .compute_distance(graphs, Some(node_features), &config.erase_label())
     }
 
     fn validate_node_features(
         &self,
-        graphs: &[GraphType],
+        graphs: &[WlGraph],
         node_features: &Array2<f64>,
-    ) -> Result<(), String> {
+    ) -> Result<(), WwlError> {
         let (num_graphs, max_nodes) = node_features.dim();
 
         if num_graphs != graphs.len() {
-            return Err(format!(
-                "Node features has {} graphs but {} graphs provided",
-                num_graphs,
-                graphs.len()
-            ));
+            return Err(WwlError::ShapeMismatch {
+                context: "Graphs in node features".to_string(),
+                expected: graphs.len(),
+                found: num_graphs,
+            });
         }
 
         let actual_max_nodes = graphs.iter().map(|g| g.node_count()).max().unwrap_or(0);
         if max_nodes < actual_max_nodes {
-            return Err(format!(
-                "Node features has {} node slots but largest graph has {} nodes",
-                max_nodes, actual_max_nodes
-            ));
+            return Err(WwlError::ShapeMismatch {
+                context: "Node slots in node features (largest graph)".to_string(),
+                expected: actual_max_nodes,
+                found: max_nodes,
+            });
         }
 
         Ok(())
     }
 
-    fn compute_kernel_impl(
+    fn validate_attributes(
         &self,
-        graphs: &[GraphType],
-        node_features: Option<&Array2<f64>>,
-        config: &KernelConfig,
-    ) -> Result<Array2<f64>, String> {
-        Python::attach(|py| {
-            let wwl_module = self.wwl.bind(py);
-            let py_graphs = self
-                .convert_graphs_to_python(py, graphs)
-                .map_err(|e| format!("Graph conversion failed: {}", e))?;
-
-            let kwargs = PyDict::new(py);
-
-            if let Some(features) = node_features {
-                let py_features = PyArray2::from_array(py, features);
-                kwargs.set_item("node_features", py_features).unwrap();
+        graphs: &[WlGraph],
+        node_features: &[Array2<f64>],
+    ) -> Result<(), WwlError> {
+        if node_features.len() != graphs.len() {
+            return Err(WwlError::ShapeMismatch {
+                context: "Feature matrices".to_string(),
+                expected: graphs.len(),
+                found: node_features.len(),
+            });
+        }
+
+        let dims = node_features.first().map_or(0, |f| f.ncols());
+        for (index, (graph, features)) in graphs.iter().zip(node_features).enumerate() {
+            if features.nrows() != graph.node_count() {
+                return Err(WwlError::ShapeMismatch {
+                    context: format!("Feature rows of graph {}", index),
+                    expected: graph.node_count(),
+                    found: features.nrows(),
+                });
             }
-            kwargs
-                .set_item("num_iterations", config.num_iterations)
-                .unwrap();
-            kwargs.set_item("sinkhorn", config.sinkhorn).unwrap();
-            if let Some(g) = config.gamma {
-                kwargs.set_item("gamma", g).unwrap();
+            if features.ncols() != dims {
+                return Err(WwlError::ShapeMismatch {
+                    context: format!("Feature dimensions of graph {}", index),
+                    expected: dims,
+                    found: features.ncols(),
+                });
             }
-
-            // Call WWL with explicit node_features=None for categorical mode
-            if node_features.is_none() {
-                kwargs.set_item("node_features", py.None()).unwrap();
+            if let Some(&weight) = (0..graph.node_count())
+                .flat_map(|node| graph.edge_weights(node))
+                .find(|weight| !(weight.is_finite() && **weight >= 0.0))
+            {
+                return Err(WwlError::InvalidEdgeWeight {
+                    graph: index,
+                    weight,
+                });
             }
-
-            let result = wwl_module
-                .getattr("wwl")
-                .and_then(|f| f.call((&py_graphs,), Some(&kwargs)))
-                .map_err(|e| format!("WWL computation failed: {}", e))?;
-
-            result
-                .downcast::<PyArray2<f64>>()
-                .map(|py_array| py_array.readonly().as_array().to_owned())
-                .map_err(|e| format!("Result extraction failed: {}", e))
-        })
-    }
-
-    fn compute_distance_impl(
-        &self,
-        graphs: &[GraphType],
-        node_features: Option<&Array2<f64>>,
-        config: &DistanceConfig,
-    ) -> Result<Array2<f64>, String> {
-        Python::attach(|py| {
-            let wwl_module = self.wwl.bind(py);
-            let py_graphs = self
-                .convert_graphs_to_python(py, graphs)
-                .map_err(|e| format!("Graph conversion failed: {}", e))?;
-
-            let kwargs = PyDict::new(py);
-
-            if let Some(features) = node_features {
-                let py_features = PyArray2::from_array(py, features);
-                kwargs.set_item("node_features", py_features).unwrap();
+            if let Some(((row, _), &value)) = features
+                .indexed_iter()
+                .find(|(_, value)| !value.is_finite())
+            {
+                return Err(WwlError::InvalidNodeFeature {
+                    graph: index,
+                    node: graph.node_index(row),
+                    value,
+                });
             }
-            kwargs
-                .set_item("num_iterations", config.num_iterations)
-                .unwrap();
-            kwargs.set_item("sinkhorn", config.sinkhorn).unwrap();
-            kwargs
-                .set_item("enforce_continuous", config.enforce_continuous)
-                .unwrap();
-
-            let result = wwl_module
-                .getattr("pairwise_wasserstein_distance")
-                .and_then(|f| f.call((&py_graphs,), Some(&kwargs)))
-                .map_err(|e| format!("Wasserstein distance computation failed: {}", e))?;
+        }
 
-            result
-                .downcast::<PyArray2<f64>>()
-                .map(|py_array| py_array.readonly().as_array().to_owned())
-                .map_err(|e| format!("Result extraction failed: {}", e))
-        })
+        Ok(())
     }
 
     /// Legacy method - use compute_kernel_categorical or compute_kernel_continuous instead
@@ -244,13 +836,17 @@ impl WWLKernel {
         num_iterations: Option<usize>,
         sinkhorn: Option<bool>,
         gamma: Option<f64>,
-    ) -> Result<Array2<f64>, String> {
+    ) -> Result<Array2<f64>, WwlError> {
         let config = KernelConfig {
             num_iterations: num_iterations.unwrap_or(3),
             sinkhorn: sinkhorn.unwrap_or(false),
             gamma,
+            ..Default::default()
         };
-        self.compute_kernel_impl(graphs, node_features, &config)
+        match node_features {
+            Some(node_features) => self.compute_kernel_continuous(graphs, node_features, &config),
+            None => self.compute_kernel_categorical(graphs, &config),
+        }
     }
 
     /// Legacy method - use compute_distance_categorical or compute_distance_continuous instead
@@ -262,66 +858,121 @@ impl WWLKernel {
         num_iterations: Option<usize>,
         sinkhorn: Option<bool>,
         enforce_continuous: Option<bool>,
-    ) -> Result<Array2<f64>, String> {
+    ) -> Result<Array2<f64>, WwlError> {
         let config = DistanceConfig {
             num_iterations: num_iterations.unwrap_or(3),
             sinkhorn: sinkhorn.unwrap_or(false),
             enforce_continuous: enforce_continuous.unwrap_or(false),
+            ..Default::default()
         };
-        self.compute_distance_impl(graphs, node_features, &config)
+        match node_features {
+            Some(node_features) => self.compute_distance_continuous(graphs, node_features, &config),
+            None => self.compute_distance_categorical(graphs, &config),
+        }
     }
+}
 
-    fn convert_graphs_to_python(&self, py: Python, graphs: &[GraphType]) -> PyResult<Py<PyList>> {
-        let igraph = py.import("igraph")?;
-
-        // WWL should handle different graph sizes automatically
-
-        let py_graphs: PyResult<Vec<_>> = graphs
-            .iter()
-            .map(|graph| {
-                // Convert edges to list of tuples for igraph
-                let edges: Vec<(usize, usize)> = graph
-                    .edge_indices()
-                    .map(|edge_idx| {
-                        let (a, b) = graph.edge_endpoints(edge_idx).unwrap();
-                        (a.index(), b.index())
-                    })
-                    .collect();
-
-                let node_labels: Vec<Option<i32>> = graph.node_weights().cloned().collect();
-
-                // Create igraph - must specify directed=False for undirected graphs
-                let kwargs = PyDict::new(py);
-
-                // Use pythonize to convert edges to Python list of tuples
-                let py_edges = pythonize(py, &edges)?;
-                kwargs.set_item("edges", py_edges)?;
-                kwargs.set_item("n", graph.node_count())?;
-                kwargs.set_item("directed", false)?;
+fn topologies<G: GraphInput>(graphs: &[G]) -> Vec<WlGraph> {
+    graphs.iter().map(G::to_wl_graph).collect()
+}
 
-                let py_graph = igraph.getattr("Graph")?.call((), Some(&kwargs))?;
+/// Applies the laplacian kernel to the distance matrix of every iteration
+fn per_iteration_kernels(distances: Vec<Array2<f64>>, gamma: Option<f64>) -> Vec<Array2<f64>> {
+    distances
+        .iter()
+        .map(|distances| laplacian_kernel(distances, distances, gamma))
+        .collect()
+}
 
-                // Always set labels if any nodes have labels
-                if node_labels.iter().any(|label| label.is_some()) {
-                    let labels: Vec<i32> =
-                        node_labels.iter().map(|label| label.unwrap_or(0)).collect();
+/// Interns two graphs into one label space
+fn intern_pair<G: LabeledGraphInput>(
+    first: &G,
+    second: &G,
+    missing: &MissingLabelStrategy<G::Label>,
+) -> Result<[WlGraph; 2], WwlError> {
+    let (mut nodes, mut edges) = (LabelDictionary::new(), LabelDictionary::new());
+    let graphs = [
+        first.to_labeled_wl_graph(&mut nodes, &mut edges, missing)?,
+        second.to_labeled_wl_graph(&mut nodes, &mut edges, missing)?,
+    ];
+    graph::reject_missing_labels(&graphs, missing)?;
+    Ok(graphs)
+}
 
-                    // Use pythonize to ensure proper conversion
-                    let py_labels = pythonize(py, &labels)?;
+/// Normalises two graphs along with their node features
+fn attributed_pair<G: GraphInput>(
+    (first, first_features): (&G, &Array2<f64>),
+    (second, second_features): (&G, &Array2<f64>),
+) -> ([WlGraph; 2], [Array2<f64>; 2]) {
+    (
+        [first.to_wl_graph(), second.to_wl_graph()],
+        [first_features.clone(), second_features.clone()],
+    )
+}
 
-                    let vs = py_graph.getattr("vs")?;
-                    vs.set_item("label", py_labels)?;
-                }
+/// Interns training graphs followed by test graphs into one label space
+fn intern_collections<G: LabeledGraphInput>(
+    train: &[G],
+    test: &[G],
+    missing: &MissingLabelStrategy<G::Label>,
+) -> Result<Vec<WlGraph>, WwlError> {
+    let mut dictionaries = (LabelDictionary::new(), LabelDictionary::new());
+    let mut graphs = intern_graphs_with(train, &mut dictionaries, missing)?;
+    let test = intern_graphs_with(test, &mut dictionaries, missing).map_err(|err| match err {
+        WwlError::MissingLabels { graphs } => WwlError::MissingLabels {
+            graphs: graphs.iter().map(|index| train.len() + index).collect(),
+        },
+        err => err,
+    })?;
+    graphs.extend(test);
+    Ok(graphs)
+}
 
-                Ok(py_graph.clone())
-            })
-            .collect();
+/// Concatenates training and test graphs with their node features
+fn attributed_collections<G: GraphInput>(
+    train: &[G],
+    train_features: &[Array2<f64>],
+    test: &[G],
+    test_features: &[Array2<f64>],
+) -> Result<(Vec<WlGraph>, Vec<Array2<f64>>), WwlError> {
+    for (context, graphs, features) in [
+        (
+            "Training feature matrices",
+            train.len(),
+            train_features.len(),
+        ),
+        ("Test feature matrices", test.len(), test_features.len()),
+    ] {
+        if graphs != features {
+            return Err(WwlError::ShapeMismatch {
+                context: context.to_string(),
+                expected: graphs,
+                found: features,
+            });
+        }
+    }
 
-        let py_graphs_vec = py_graphs?;
-        let py_list = PyList::new(py, py_graphs_vec)?;
+    let graphs = train.iter().chain(test).map(G::to_wl_graph).collect();
+    let features = train_features
+        .iter()
+        .chain(test_features)
+        .cloned()
+        .collect();
+    Ok((graphs, features))
+}
 
-        Ok(py_list.unbind())
-    }
+/// Splits a zero padded `graphs × max_nodes` matrix into one single column
+/// feature matrix per graph
+fn split_node_features(graphs: &[WlGraph], node_features: &Array2<f64>) -> Vec<Array2<f64>> {
+    graphs
+        .iter()
+        .zip(node_features.rows())
+        .map(|(graph, row)| {
+            row.slice(ndarray::s![..graph.node_count()])
+                .insert_axis(ndarray::Axis(1))
+                .to_owned()
+        })
+        .collect()
 }
 
 #[cfg(test)]
@@ -329,33 +980,72 @@ mod tests {
     use super::*;
     use petgraph::Graph;
 
+    /// Backend for tests that must fail before reaching any computation
+    struct Unreachable;
+
+    impl Backend for Unreachable {
+        fn compute_kernel(
+            &self,
+            _: &[WlGraph],
+            _: Option<&[Array2<f64>]>,
+            _: &KernelConfig<()>,
+        ) -> Result<Array2<f64>, WwlError> {
+            unreachable!()
+        }
+
+        fn compute_distance(
+            &self,
+            _: &[WlGraph],
+            _: Option<&[Array2<f64>]>,
+            _: &DistanceConfig<()>,
+        ) -> Result<Array2<f64>, WwlError> {
+            unreachable!()
+        }
+    }
+
     #[test]
     fn test_config_defaults() {
-        let kernel_config = KernelConfig::default();
+        let kernel_config: KernelConfig = KernelConfig::default();
         assert_eq!(kernel_config.num_iterations, 3);
         assert!(!kernel_config.sinkhorn);
         assert!(kernel_config.gamma.is_none());
 
-        let distance_config = DistanceConfig::default();
+        let distance_config: DistanceConfig = DistanceConfig::default();
         assert_eq!(distance_config.num_iterations, 3);
         assert!(!distance_config.sinkhorn);
         assert!(!distance_config.enforce_continuous);
+        assert!(matches!(
+            distance_config.missing_labels,
+            MissingLabelStrategy::Degree
+        ));
+        assert!(distance_config.num_threads.is_none());
+
+        let sinkhorn_config = SinkhornConfig::default();
+        assert_eq!(sinkhorn_config.epsilon, 1e-2);
+        assert_eq!(sinkhorn_config.max_iterations, 1000);
+        assert!(sinkhorn_config.log_domain);
+        assert!(!sinkhorn_config.require_convergence);
     }
 
     #[test]
     fn test_node_features_validation() {
         match WWLKernel::new() {
             Ok(kernel) => {
-                let mut graph = Graph::new_undirected();
+                let mut graph: GraphType = Graph::new_undirected();
                 graph.add_node(None);
                 graph.add_node(None);
-                let graphs = vec![graph];
+                let graphs = vec![graph.to_wl_graph()];
 
                 // Wrong number of graphs
                 let bad_features = Array2::zeros((2, 2)); // 2 graphs, but only 1 provided
-                assert!(kernel
-                    .validate_node_features(&graphs, &bad_features)
-                    .is_err());
+                assert!(matches!(
+                    kernel.validate_node_features(&graphs, &bad_features),
+                    Err(WwlError::ShapeMismatch {
+                        expected: 1,
+                        found: 2,
+                        ..
+                    })
+                ));
 
                 // Too few node slots
                 let bad_features = Array2::zeros((1, 1)); // 1 node slot, but graph has 2 nodes
@@ -375,6 +1065,628 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_config_validation() {
+        assert!(KernelConfig::<i32>::default().validate().is_ok());
+        assert!(DistanceConfig::<i32>::default().validate().is_ok());
+
+        let config: KernelConfig = KernelConfig {
+            gamma: Some(0.0),
+            ..Default::default()
+        };
+        assert!(matches!(config.validate(), Err(WwlError::InvalidConfig(_))));
+
+        let config: KernelConfig = KernelConfig {
+            num_threads: Some(0),
+            ..Default::default()
+        };
+        assert!(matches!(config.validate(), Err(WwlError::InvalidConfig(_))));
+
+        let config: DistanceConfig = DistanceConfig {
+            sinkhorn_config: SinkhornConfig {
+                epsilon: -1.0,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert!(matches!(config.validate(), Err(WwlError::InvalidConfig(_))));
+    }
+
+    #[test]
+    fn test_missing_labels_rejected_before_backend() {
+        let kernel = WWLKernel::with_backend(Unreachable);
+        let mut labeled: GraphType = Graph::new_undirected();
+        labeled.add_node(Some(1));
+        let mut unlabeled: GraphType = Graph::new_undirected();
+        unlabeled.add_node(None);
+
+        let config = KernelConfig {
+            missing_labels: MissingLabelStrategy::Error,
+            ..Default::default()
+        };
+        assert_eq!(
+            kernel.compute_kernel_categorical(&[labeled, unlabeled], &config),
+            Err(WwlError::MissingLabels { graphs: vec![1] })
+        );
+    }
+
+    #[test]
+    fn test_cross_validation() {
+        let kernel = WWLKernel::with_backend(Unreachable);
+        let mut labeled: GraphType = Graph::new_undirected();
+        labeled.add_node(Some(1));
+        let mut unlabeled: GraphType = Graph::new_undirected();
+        unlabeled.add_node(None);
+
+        let config = DistanceConfig {
+            missing_labels: MissingLabelStrategy::Error,
+            ..Default::default()
+        };
+        assert_eq!(
+            kernel.compute_cross_distance_categorical(
+                &[labeled.clone(), labeled.clone()],
+                &[labeled.clone(), unlabeled],
+                &config
+            ),
+            Err(WwlError::MissingLabels { graphs: vec![3] })
+        );
+
+        let features = Array2::zeros((1, 4));
+        assert!(matches!(
+            kernel.compute_cross_distance_attributed(
+                &[labeled.clone()],
+                &[],
+                &[labeled],
+                &[features.clone(), features],
+                &DistanceConfig::default()
+            ),
+            Err(WwlError::ShapeMismatch {
+                expected: 1,
+                found: 0,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_attribute_validation() {
+        let kernel = WWLKernel::with_backend(Unreachable);
+        let mut graph: GraphType = Graph::new_undirected();
+        graph.add_node(None);
+        graph.add_node(None);
+        let graphs = vec![graph.to_wl_graph(), graph.to_wl_graph()];
+
+        let good = vec![Array2::zeros((2, 16)), Array2::zeros((2, 16))];
+        assert!(kernel.validate_attributes(&graphs, &good).is_ok());
+
+        let missing_node = vec![Array2::zeros((2, 16)), Array2::zeros((1, 16))];
+        assert!(matches!(
+            kernel.validate_attributes(&graphs, &missing_node),
+            Err(WwlError::ShapeMismatch {
+                expected: 2,
+                found: 1,
+                ..
+            })
+        ));
+
+        let mixed_dims = vec![Array2::zeros((2, 16)), Array2::zeros((2, 8))];
+        assert!(matches!(
+            kernel.validate_attributes(&graphs, &mixed_dims),
+            Err(WwlError::ShapeMismatch {
+                expected: 16,
+                found: 8,
+                ..
+            })
+        ));
+
+        let mut infinite = good.clone();
+        infinite[1][[1, 3]] = f64::INFINITY;
+        assert_eq!(
+            kernel.validate_attributes(&graphs, &infinite),
+            Err(WwlError::InvalidNodeFeature {
+                graph: 1,
+                node: 1,
+                value: f64::INFINITY
+            })
+        );
+        let nan = vec![good[0].clone(), Array2::from_elem((2, 16), f64::NAN)];
+        assert!(matches!(
+            kernel.compute_distance_attributed(&[graph.clone(), graph.clone()], &nan, &DistanceConfig::default()),
+            Err(WwlError::InvalidNodeFeature { graph: 1, node: 0, value }) if value.is_nan()
+        ));
+        let padded = ndarray::array![[0.0, 1.0, f64::NAN], [f64::NAN, 1.0, 2.0]];
+        assert!(matches!(
+            kernel.compute_kernel_continuous(
+                &[graph.clone(), graph.clone()],
+                &padded,
+                &KernelConfig::default()
+            ),
+            Err(WwlError::InvalidNodeFeature {
+                graph: 1,
+                node: 0,
+                ..
+            })
+        ));
+
+        let mut weighted = Graph::<(), f64, Undirected>::new_undirected();
+        let a = weighted.add_node(());
+        let b = weighted.add_node(());
+        weighted.add_edge(a, b, -1.0);
+        assert_eq!(
+            kernel.compute_kernel_weighted(&[weighted], &good[..1], &KernelConfig::default()),
+            Err(WwlError::InvalidEdgeWeight {
+                graph: 0,
+                weight: -1.0
+            })
+        );
+    }
+
+    #[test]
+    fn test_split_node_features() {
+        let mut graph: GraphType = Graph::new_undirected();
+        graph.add_node(None);
+        let graphs = vec![graph.to_wl_graph()];
+
+        let features = ndarray::array![[1.5, 0.0, 0.0]];
+        let split = split_node_features(&graphs, &features);
+        assert_eq!(split, vec![ndarray::array![[1.5]]]);
+    }
+
+    #[cfg(feature = "native")]
+    #[test]
+    fn test_generic_graph_input() {
+        use petgraph::stable_graph::StableUnGraph;
+
+        // Same reference pair as the examples, with a removed node in between
+        let mut graph1 = StableUnGraph::<i32, ()>::default();
+        let n1 = graph1.add_node(1);
+        let removed = graph1.add_node(0);
+        let n2 = graph1.add_node(2);
+        graph1.add_edge(n1, n2, ());
+        graph1.remove_node(removed);
+
+        let mut graph2 = StableUnGraph::<i32, ()>::default();
+        let n3 = graph2.add_node(1);
+        let n4 = graph2.add_node(2);
+        let n5 = graph2.add_node(3);
+        graph2.add_edge(n3, n4, ());
+        graph2.add_edge(n4, n5, ());
+
+        let kernel = WWLKernel::native();
+        let distances = kernel
+            .compute_distance_categorical(&[graph1, graph2], &DistanceConfig::default())
+            .unwrap();
+        assert!((distances[[0, 1]] - 0.75).abs() < 1e-6);
+    }
+
+    #[cfg(feature = "native")]
+    #[test]
+    fn test_single_pair_matches_matrix() {
+        let mut graph1: GraphType = Graph::new_undirected();
+        let n1 = graph1.add_node(Some(1));
+        let n2 = graph1.add_node(Some(4));
+        graph1.add_edge(n1, n2, ());
+        let mut graph2: GraphType = Graph::new_undirected();
+        let n3 = graph2.add_node(Some(1));
+        let n4 = graph2.add_node(Some(2));
+        let n5 = graph2.add_node(None);
+        graph2.add_edge(n3, n4, ());
+        graph2.add_edge(n4, n5, ());
+        let graphs = [graph1, graph2];
+
+        let kernel = WWLKernel::native();
+        let config = KernelConfig {
+            gamma: Some(0.3),
+            ..Default::default()
+        };
+        let distance = kernel
+            .distance(&graphs[0], &graphs[1], &DistanceConfig::default())
+            .unwrap();
+        let distances = kernel
+            .compute_distance_categorical(&graphs, &DistanceConfig::default())
+            .unwrap();
+        assert_eq!(distance, distances[[0, 1]]);
+
+        for config in [config.clone(), KernelConfig::default()] {
+            let value = kernel
+                .kernel_value(&graphs[0], &graphs[1], &config)
+                .unwrap();
+            let matrix = kernel.compute_kernel_categorical(&graphs, &config).unwrap();
+            assert!((value - matrix[[0, 1]]).abs() < 1e-12);
+        }
+        let value = kernel
+            .kernel_value(&graphs[0], &graphs[1], &KernelConfig::default())
+            .unwrap();
+        assert!((value - (-distance).exp()).abs() < 1e-12);
+
+        let features = [
+            ndarray::array![[0.5], [1.0]],
+            ndarray::array![[0.5], [1.0], [2.0]],
+        ];
+        let distance = kernel
+            .distance_attributed(
+                (&graphs[0], &features[0]),
+                (&graphs[1], &features[1]),
+                &DistanceConfig::default(),
+            )
+            .unwrap();
+        let distances = kernel
+            .compute_distance_attributed(&graphs, &features, &DistanceConfig::default())
+            .unwrap();
+        assert_eq!(distance, distances[[0, 1]]);
+
+        let value = kernel
+            .kernel_value_attributed(
+                (&graphs[0], &features[0]),
+                (&graphs[1], &features[1]),
+                &config,
+            )
+            .unwrap();
+        let matrix = kernel
+            .compute_kernel_attributed(&graphs, &features, &config)
+            .unwrap();
+        assert!((value - matrix[[0, 1]]).abs() < 1e-12);
+    }
+
+    #[cfg(feature = "native")]
+    #[test]
+    fn test_transport_plan_node_indices() {
+        use petgraph::graph::NodeIndex;
+        use petgraph::stable_graph::StableUnGraph;
+
+        let mut first = StableUnGraph::<&str, ()>::default();
+        let removed = first.add_node("H");
+        let c = first.add_node("C");
+        let o = first.add_node("O");
+        first.add_edge(c, o, ());
+        first.remove_node(removed);
+
+        let mut second = StableUnGraph::<&str, ()>::default();
+        let o2 = second.add_node("O");
+        let c2 = second.add_node("C");
+        second.add_edge(o2, c2, ());
+
+        let alignment = WWLKernel::native()
+            .transport_plan(&first, &second, &DistanceConfig::default())
+            .unwrap();
+        assert_eq!(alignment.distance, 0.0);
+        assert_eq!(
+            alignment.couplings,
+            vec![
+                Coupling {
+                    source: NodeIndex::new(1),
+                    target: NodeIndex::new(1),
+                    mass: 0.5
+                },
+                Coupling {
+                    source: NodeIndex::new(2),
+                    target: NodeIndex::new(0),
+                    mass: 0.5
+                },
+            ]
+        );
+        assert_eq!(alignment.couplings_from(c).count(), 1);
+
+        // Backends without their own solver keep the default
+        assert!(matches!(
+            WWLKernel::with_backend(Unreachable).transport_plan(
+                &first,
+                &second,
+                &DistanceConfig::default()
+            ),
+            Err(WwlError::Unsupported(_))
+        ));
+    }
+
+    #[cfg(feature = "native")]
+    #[test]
+    fn test_per_iteration_matches_single_runs() {
+        let mut graph1: GraphType = Graph::new_undirected();
+        let n1 = graph1.add_node(Some(1));
+        let n2 = graph1.add_node(Some(2));
+        graph1.add_edge(n1, n2, ());
+        let mut graph2: GraphType = Graph::new_undirected();
+        let n3 = graph2.add_node(Some(1));
+        let n4 = graph2.add_node(Some(2));
+        let n5 = graph2.add_node(Some(2));
+        graph2.add_edge(n3, n4, ());
+        graph2.add_edge(n4, n5, ());
+        let graphs = [graph1, graph2];
+        let features = [
+            ndarray::array![[1.0, 0.0], [2.0, 1.0]],
+            ndarray::array![[1.0, 0.0], [2.0, 1.0], [0.5, 0.5]],
+        ];
+
+        let kernel = WWLKernel::native();
+        let kernels = kernel
+            .compute_kernel_per_iteration_categorical(&graphs, &KernelConfig::default())
+            .unwrap();
+        let distances = kernel
+            .compute_distance_per_iteration_attributed(
+                &graphs,
+                &features,
+                &DistanceConfig::default(),
+            )
+            .unwrap();
+        assert_eq!(kernels.len(), 4);
+        assert_eq!(distances.len(), 4);
+
+        for h in 0..=3 {
+            let kernel_config = KernelConfig {
+                num_iterations: h,
+                ..Default::default()
+            };
+            let distance_config = DistanceConfig {
+                num_iterations: h,
+                ..Default::default()
+            };
+            assert_eq!(
+                kernels[h],
+                kernel
+                    .compute_kernel_categorical(&graphs, &kernel_config)
+                    .unwrap()
+            );
+            assert_eq!(
+                distances[h],
+                kernel
+                    .compute_distance_attributed(&graphs, &features, &distance_config)
+                    .unwrap()
+            );
+        }
+    }
+
+    #[cfg(feature = "native")]
+    #[test]
+    fn test_sinkhorn_non_convergence() {
+        let mut graph1: GraphType = Graph::new_undirected();
+        let n1 = graph1.add_node(Some(1));
+        let n2 = graph1.add_node(Some(2));
+        graph1.add_edge(n1, n2, ());
+        let mut graph2: GraphType = Graph::new_undirected();
+        let n3 = graph2.add_node(Some(1));
+        let n4 = graph2.add_node(Some(2));
+        let n5 = graph2.add_node(Some(3));
+        graph2.add_edge(n3, n4, ());
+        graph2.add_edge(n4, n5, ());
+        let mut graph3: GraphType = Graph::new_undirected();
+        let n6 = graph3.add_node(Some(3));
+        let n7 = graph3.add_node(Some(3));
+        let n8 = graph3.add_node(Some(1));
+        graph3.add_edge(n6, n7, ());
+        graph3.add_edge(n7, n8, ());
+        let graphs = [graph1, graph2, graph3];
+
+        let kernel = WWLKernel::native();
+        let config = DistanceConfig {
+            sinkhorn: true,
+            sinkhorn_config: SinkhornConfig {
+                max_iterations: 1,
+                require_convergence: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let result = kernel.compute_distance_categorical(&graphs, &config);
+        assert!(matches!(
+            result,
+            Err(WwlError::NotConverged { iterations: 1, .. })
+        ));
+
+        // Without `require_convergence` the last iterate is used
+        let mut lenient = config.clone();
+        lenient.sinkhorn_config.require_convergence = false;
+        assert!(kernel
+            .compute_distance_categorical(&graphs, &lenient)
+            .is_ok());
+
+        let config = DistanceConfig {
+            sinkhorn: true,
+            sinkhorn_config: SinkhornConfig {
+                epsilon: 0.1,
+                max_iterations: 1000,
+                tolerance: 1e-6,
+                require_convergence: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let distances = kernel
+            .compute_distance_categorical(&graphs, &config)
+            .unwrap();
+        assert!(distances[[0, 1]] > 0.0);
+    }
+
+    #[cfg(feature = "native")]
+    #[test]
+    fn test_sinkhorn_defaults_on_small_graphs() {
+        let path = |labels: &[i32]| {
+            let mut graph: GraphType = Graph::new_undirected();
+            let nodes: Vec<_> = labels.iter().map(|&l| graph.add_node(Some(l))).collect();
+            for pair in nodes.windows(2) {
+                graph.add_edge(pair[0], pair[1], ());
+            }
+            graph
+        };
+        // The last path ties several transport plans
+        let paths = [
+            path(&[0, 1]),
+            path(&[0, 1, 2]),
+            path(&[0, 1, 2, 3]),
+            path(&[3, 3, 1]),
+        ];
+
+        let kernel = WWLKernel::native();
+        let distance_config = DistanceConfig {
+            sinkhorn: true,
+            ..Default::default()
+        };
+        let distances = kernel
+            .compute_distance_categorical(&paths, &distance_config)
+            .unwrap();
+        let exact = kernel
+            .compute_distance_categorical(&paths, &DistanceConfig::default())
+            .unwrap();
+        assert!(distances
+            .iter()
+            .zip(&exact)
+            .all(|(sinkhorn, exact)| (sinkhorn - exact).abs() < 0.05));
+
+        let kernel_config = KernelConfig {
+            sinkhorn: true,
+            ..Default::default()
+        };
+        assert!(kernel
+            .compute_kernel_categorical(&paths, &kernel_config)
+            .is_ok());
+    }
+
+    #[cfg(feature = "native")]
+    #[test]
+    fn test_enforce_continuous_reads_label_values() {
+        let path = |labels: &[i32]| {
+            let mut graph: GraphType = Graph::new_undirected();
+            let nodes: Vec<_> = labels.iter().map(|&l| graph.add_node(Some(l))).collect();
+            for pair in nodes.windows(2) {
+                graph.add_edge(pair[0], pair[1], ());
+            }
+            graph
+        };
+        let (a, b) = (path(&[100, 5]), path(&[5, 7, 100]));
+
+        let kernel = WWLKernel::native();
+        let config = DistanceConfig {
+            enforce_continuous: true,
+            ..Default::default()
+        };
+        let forward = kernel
+            .compute_distance_categorical(&[a.clone(), b.clone()], &config)
+            .unwrap();
+        let backward = kernel
+            .compute_distance_categorical(&[b.clone(), a.clone()], &config)
+            .unwrap();
+        assert!(forward[[0, 1]] > 0.0);
+        assert!((forward[[0, 1]] - backward[[0, 1]]).abs() < 1e-9);
+
+        // The label values are the node features
+        let features = [
+            ndarray::array![[100.0], [5.0]],
+            ndarray::array![[5.0], [7.0], [100.0]],
+        ];
+        let attributed = kernel
+            .compute_distance_attributed(&[a, b], &features, &config)
+            .unwrap();
+        assert!((forward[[0, 1]] - attributed[[0, 1]]).abs() < 1e-9);
+    }
+
+    #[cfg(feature = "native")]
+    #[test]
+    fn test_concurrent_use() {
+        use std::sync::Arc;
+        use std::thread;
+
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<WWLKernel>();
+
+        let mut graph1: GraphType = Graph::new_undirected();
+        let n1 = graph1.add_node(Some(1));
+        let n2 = graph1.add_node(Some(2));
+        graph1.add_edge(n1, n2, ());
+        let mut graph2 = graph1.clone();
+        let n3 = graph2.add_node(Some(3));
+        graph2.add_edge(n2, n3, ());
+        let graphs = Arc::new(vec![graph1, graph2]);
+
+        let kernel = Arc::new(WWLKernel::native());
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let kernel = Arc::clone(&kernel);
+                let graphs = Arc::clone(&graphs);
+                thread::spawn(move || {
+                    kernel
+                        .compute_distance_categorical(&graphs, &DistanceConfig::default())
+                        .unwrap()
+                })
+            })
+            .collect();
+        for handle in handles {
+            assert!((handle.join().unwrap()[[0, 1]] - 0.75).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_node_embeddings_and_histograms() {
+        let kernel = WWLKernel::with_backend(Unreachable);
+        let mut graph1: GraphType = Graph::new_undirected();
+        let n1 = graph1.add_node(Some(1));
+        let n2 = graph1.add_node(Some(2));
+        graph1.add_edge(n1, n2, ());
+        let mut graph2 = graph1.clone();
+        let n3 = graph2.add_node(Some(3));
+        graph2.add_edge(n2, n3, ());
+        let graphs = [graph1, graph2];
+        let config = DistanceConfig::default();
+
+        let embeddings = kernel
+            .node_embeddings_categorical(&graphs, &config)
+            .unwrap();
+        assert_eq!(embeddings[0].dim(), (2, 4));
+        assert_eq!(embeddings[1].dim(), (3, 4));
+
+        let histograms = kernel
+            .label_histograms_categorical(&graphs, &config)
+            .unwrap();
+        assert_eq!(histograms.nrows(), 2);
+        // Every node is counted once per iteration
+        assert_eq!(histograms.row(1).sum(), 3.0 * 4.0);
+
+        let features = vec![Array2::ones((2, 2)), Array2::ones((3, 2))];
+        let embeddings = kernel
+            .node_embeddings_attributed(&graphs, &features, &config)
+            .unwrap();
+        assert_eq!(embeddings[1].dim(), (3, 8));
+        assert!(kernel
+            .node_embeddings_attributed(&graphs, &features[..1], &config)
+            .is_err());
+    }
+
+    #[cfg(feature = "native")]
+    #[test]
+    fn test_cached_embeddings_across_collections() {
+        let path = |labels: &[i32]| {
+            let mut graph: GraphType = Graph::new_undirected();
+            let nodes: Vec<_> = labels.iter().map(|&l| graph.add_node(Some(l))).collect();
+            for pair in nodes.windows(2) {
+                graph.add_edge(pair[0], pair[1], ());
+            }
+            graph
+        };
+        let (a, b, c) = (path(&[1, 2]), path(&[1, 2, 3]), path(&[3, 1]));
+
+        let cache = EmbeddingCache::new();
+        let cached = WWLKernel::with_backend(NativeBackend::with_cache(cache.clone()));
+        let uncached = WWLKernel::native();
+        let config = DistanceConfig::default();
+
+        let first = cached
+            .compute_distance_categorical(&[a.clone(), b.clone()], &config)
+            .unwrap();
+        assert!((first[[0, 1]] - 0.75).abs() < 1e-6);
+        assert_eq!(cache.stats().misses, 2);
+
+        // `b` is interned first this time, yet its entry is reused
+        let collection = [b, c, a];
+        let second = cached
+            .compute_distance_categorical(&collection, &config)
+            .unwrap();
+        assert_eq!(
+            second,
+            uncached
+                .compute_distance_categorical(&collection, &config)
+                .unwrap()
+        );
+        assert_eq!(cache.stats(), CacheStats { hits: 2, misses: 3 });
+    }
+
     #[test]
     fn test_graph_type_creation() {
         let mut graph: GraphType = Graph::new_undirected();
@@ -0,0 +1,297 @@
+//! Fitted WWL models
+//!
+//! [`WwlModel`] keeps the label space of a training collection so that graphs
+//! seen later are embedded consistently with it, instead of re-embedding the
+//! whole collection on every call.
+
+use std::hash::Hash;
+
+use ndarray::Array2;
+
+#[cfg(feature = "native")]
+use crate::backend::{laplacian_kernel, NativeBackend};
+use crate::graph::{intern_graphs_with, LabelDictionary, LabeledGraphInput};
+use crate::propagation::WlCompression;
+#[cfg(feature = "native")]
+use crate::validate_gamma;
+use crate::{DistanceConfig, WwlError};
+
+/// Categorical WWL embedding fitted on a training collection
+///
+/// Fitting interns the node and edge labels of the training graphs, runs
+/// categorical propagation and keeps both the WL compression tables and the
+/// training embeddings. [`WwlModel::transform`] then embeds new graphs into
+/// the same label space: equal ids denote the same rooted subtree as in the
+/// training embeddings, and subtrees never seen while fitting get ids of
+/// their own.
+///
+/// `N` and `E` are the node and edge label types of the graphs, see
+/// [`LabeledGraphInput`].
+#[derive(Clone)]
+pub struct WwlModel<N, E = ()> {
+    config: DistanceConfig<N>,
+    dictionaries: (LabelDictionary<N>, LabelDictionary<E>),
+    compression: WlCompression,
+    embeddings: Vec<Array2<usize>>,
+    /// Solves distances between embeddings, keeping its thread pools
+    #[cfg(feature = "native")]
+    solver: NativeBackend,
+}
+
+impl<N, E> WwlModel<N, E>
+where
+    N: Hash + Eq + Clone + 'static,
+    E: Hash + Eq + Clone + 'static,
+{
+    /// Fits the model on training graphs
+    ///
+    /// Uses `config.num_iterations` and `config.missing_labels`; the solver
+    /// settings are kept for [`WwlModel::distances`] and the methods
+    /// following it.
+    pub fn fit<G>(graphs: &[G], config: &DistanceConfig<N>) -> Result<Self, WwlError>
+    where
+        G: LabeledGraphInput<Label = N, EdgeLabel = E>,
+    {
+        config.validate()?;
+        let mut dictionaries = (LabelDictionary::new(), LabelDictionary::new());
+        let graphs = intern_graphs_with(graphs, &mut dictionaries, &config.missing_labels)?;
+        let (compression, embeddings) = WlCompression::fit(&graphs, config.num_iterations);
+
+        Ok(WwlModel {
+            config: config.clone(),
+            dictionaries,
+            compression,
+            embeddings,
+            #[cfg(feature = "native")]
+            solver: NativeBackend::new(),
+        })
+    }
+
+    /// Embeds new graphs into the label space of the training graphs
+    ///
+    /// The model is left untouched, so transforming the training graphs
+    /// again reproduces [`WwlModel::embeddings`]. Labels and subtrees never
+    /// seen while fitting get ids that are only consistent within one call:
+    /// embeddings of separately transformed batches are comparable with the
+    /// training embeddings, but not with each other.
+    pub fn transform<G>(&self, graphs: &[G]) -> Result<Vec<Array2<usize>>, WwlError>
+    where
+        G: LabeledGraphInput<Label = N, EdgeLabel = E>,
+    {
+        // Unseen labels extend a copy of the dictionaries, past the fitted ids
+        let mut dictionaries = self.dictionaries.clone();
+        let graphs = intern_graphs_with(graphs, &mut dictionaries, &self.config.missing_labels)?;
+        Ok(self.compression.transform(&graphs))
+    }
+
+    /// Embeddings of the training graphs, one per graph
+    pub fn embeddings(&self) -> &[Array2<usize>] {
+        &self.embeddings
+    }
+
+    /// Configuration the model was fitted with
+    pub fn config(&self) -> &DistanceConfig<N> {
+        &self.config
+    }
+
+    /// Node labels of the training graphs
+    pub fn node_labels(&self) -> &LabelDictionary<N> {
+        &self.dictionaries.0
+    }
+
+    /// Edge labels of the training graphs
+    pub fn edge_labels(&self) -> &LabelDictionary<E> {
+        &self.dictionaries.1
+    }
+
+    /// Bag-of-labels histograms of training or transformed embeddings
+    ///
+    /// Columns are the labels met while fitting, so histograms of training
+    /// and test graphs can be fed to the same downstream model; see
+    /// [`WlCompression::histograms`].
+    pub fn histograms(&self, embeddings: &[Array2<usize>]) -> Array2<f64> {
+        self.compression.histograms(embeddings)
+    }
+
+    /// WL compression tables of the training graphs
+    pub fn compression(&self) -> &WlCompression {
+        &self.compression
+    }
+
+    /// Wasserstein distances between the training graphs
+    ///
+    /// Solved natively with the solver settings of the fitted configuration,
+    /// like [`WWLKernel::compute_distance_categorical`](crate::WWLKernel::compute_distance_categorical)
+    /// with the native backend.
+    #[cfg(feature = "native")]
+    pub fn distances(&self) -> Result<Array2<f64>, WwlError> {
+        self.solver
+            .embedding_distances(&self.embeddings, &self.config.erase_label())
+    }
+
+    /// Wasserstein distances from transformed graphs (rows) to the training
+    /// graphs (columns)
+    ///
+    /// `embeddings` must come from one call to [`WwlModel::transform`].
+    #[cfg(feature = "native")]
+    pub fn cross_distances(&self, embeddings: &[Array2<usize>]) -> Result<Array2<f64>, WwlError> {
+        self.check_embeddings(embeddings)?;
+        self.solver.embedding_cross_distances(
+            &self.embeddings,
+            embeddings,
+            &self.config.erase_label(),
+        )
+    }
+
+    /// WWL kernel matrix of the training graphs
+    ///
+    /// `gamma` is the laplacian kernel parameter of
+    /// [`KernelConfig::gamma`](crate::KernelConfig::gamma).
+    #[cfg(feature = "native")]
+    pub fn kernel(&self, gamma: Option<f64>) -> Result<Array2<f64>, WwlError> {
+        validate_gamma(gamma)?;
+        let distances = self.distances()?;
+        Ok(laplacian_kernel(&distances, &distances, gamma))
+    }
+
+    /// WWL kernel between transformed graphs (rows) and the training graphs
+    /// (columns)
+    ///
+    /// Matches [`WWLKernel::compute_cross_kernel_categorical`](crate::WWLKernel::compute_cross_kernel_categorical):
+    /// passing the training embeddings reproduces [`WwlModel::kernel`].
+    #[cfg(feature = "native")]
+    pub fn cross_kernel(
+        &self,
+        embeddings: &[Array2<usize>],
+        gamma: Option<f64>,
+    ) -> Result<Array2<f64>, WwlError> {
+        validate_gamma(gamma)?;
+        let cross = self.cross_distances(embeddings)?;
+        Ok(laplacian_kernel(&cross, &self.distances()?, gamma))
+    }
+
+    /// Checks that embeddings span the fitted number of iterations
+    #[cfg(feature = "native")]
+    fn check_embeddings(&self, embeddings: &[Array2<usize>]) -> Result<(), WwlError> {
+        let expected = self.config.num_iterations + 1;
+        match embeddings
+            .iter()
+            .find(|embedding| embedding.ncols() != expected)
+        {
+            Some(embedding) => Err(WwlError::ShapeMismatch {
+                context: "Columns of transformed embeddings".to_string(),
+                expected,
+                found: embedding.ncols(),
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GraphType;
+    use petgraph::Graph;
+
+    fn path(labels: &[i32]) -> GraphType {
+        let mut graph: GraphType = Graph::new_undirected();
+        let nodes: Vec<_> = labels.iter().map(|&l| graph.add_node(Some(l))).collect();
+        for pair in nodes.windows(2) {
+            graph.add_edge(pair[0], pair[1], ());
+        }
+        graph
+    }
+
+    #[test]
+    fn test_transform_reproduces_training_embeddings() {
+        let train = [path(&[1, 2]), path(&[1, 2, 3])];
+        let model = WwlModel::fit(&train, &DistanceConfig::default()).unwrap();
+
+        assert_eq!(model.embeddings().len(), 2);
+        assert_eq!(model.compression().num_iterations(), 3);
+        assert_eq!(model.transform(&train).unwrap(), model.embeddings());
+
+        // Order within the transformed collection does not matter
+        let reversed = model.transform(&[path(&[1, 2, 3])]).unwrap();
+        assert_eq!(reversed[0], model.embeddings()[1]);
+    }
+
+    #[test]
+    fn test_out_of_sample_labels() {
+        let train = [path(&[1, 2]), path(&[1, 2, 3])];
+        let model = WwlModel::fit(&train, &DistanceConfig::default()).unwrap();
+
+        let test = model.transform(&[path(&[1, 2, 9]), path(&[9])]).unwrap();
+        // Nodes 1 and 2 start from fitted labels, label 9 from a new id
+        assert_eq!(test[0][[0, 0]], model.embeddings()[1][[0, 0]]);
+        assert_eq!(test[0][[1, 0]], model.embeddings()[1][[1, 0]]);
+        assert!(test[0][[2, 0]] >= 3);
+        assert_eq!(test[0][[2, 0]], test[1][[0, 0]]);
+        // Node 1 now sees label 9, a neighbourhood absent from training
+        assert_ne!(test[0][[1, 1]], model.embeddings()[1][[1, 1]]);
+        // The model itself is not extended
+        assert_eq!(model.node_labels().len(), 3);
+
+        let histograms = model.histograms(&test);
+        assert_eq!(
+            histograms.ncols(),
+            model.histograms(model.embeddings()).ncols()
+        );
+        // A lone node labeled 9 matches no training label at any iteration
+        assert_eq!(histograms.row(1).sum(), 0.0);
+    }
+
+    #[cfg(feature = "native")]
+    #[test]
+    fn test_distances_match_kernel() {
+        use crate::{KernelConfig, WWLKernel};
+
+        let train = [path(&[1, 2]), path(&[1, 2, 3]), path(&[3, 3, 1])];
+        let test = [path(&[1, 2, 9]), path(&[2, 1])];
+        let model = WwlModel::fit(&train, &DistanceConfig::default()).unwrap();
+        let kernel = WWLKernel::native();
+
+        let close = |a: &Array2<f64>, b: &Array2<f64>| {
+            a.dim() == b.dim() && a.iter().zip(b).all(|(x, y)| (x - y).abs() < 1e-12)
+        };
+
+        let distances = model.distances().unwrap();
+        let expected = kernel
+            .compute_distance_categorical(&train, &DistanceConfig::default())
+            .unwrap();
+        assert_eq!(distances, expected);
+        // Each direction is solved separately, so only up to rounding
+        assert!(close(
+            &model.cross_distances(model.embeddings()).unwrap(),
+            &distances
+        ));
+
+        let embeddings = model.transform(&test).unwrap();
+        let expected = kernel
+            .compute_cross_kernel_categorical(&train, &test, &KernelConfig::default())
+            .unwrap();
+        assert!(close(
+            &model.cross_kernel(&embeddings, None).unwrap(),
+            &expected
+        ));
+        assert!(close(
+            &model.cross_kernel(model.embeddings(), None).unwrap(),
+            &model.kernel(None).unwrap()
+        ));
+
+        assert!(model.kernel(Some(-1.0)).is_err());
+        let truncated: Vec<_> = embeddings
+            .iter()
+            .map(|embedding| embedding.slice(ndarray::s![.., ..2]).to_owned())
+            .collect();
+        assert!(matches!(
+            model.cross_distances(&truncated),
+            Err(WwlError::ShapeMismatch {
+                expected: 4,
+                found: 2,
+                ..
+            })
+        ));
+    }
+}
@@ -0,0 +1,445 @@
+//! Weisfeiler-Lehman propagation schemes
+//!
+//! Native implementations of the node embedding stage of WWL. Each scheme
+//! turns a graph into one row per node holding the node representation at
+//! every iteration `h = 0..=num_iterations`.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use ndarray::{s, Array2, ArrayView1};
+
+use crate::graph::{InitialLabel, WlGraph};
+
+/// Sorted multiset of `(edge label, neighbour label)` pairs of a node
+type Signature = Vec<(Option<usize>, usize)>;
+
+/// Collects the signature of one edge direction, each neighbour contributing
+/// through the edge reaching it
+pub(crate) fn signature(
+    neighbors: &[usize],
+    edge_labels: &[Option<usize>],
+    current: &[usize],
+) -> Signature {
+    let mut signature: Signature = edge_labels
+        .iter()
+        .zip(neighbors)
+        .map(|(&edge, &n)| (edge, current[n]))
+        .collect();
+    signature.sort_unstable();
+    signature
+}
+
+/// Key of a relabeling step: own label, outgoing and incoming signatures
+type Relabeling = (usize, Signature, Signature);
+
+/// Label compression tables built by categorical propagation
+///
+/// One table maps initial labels to ids, and one table per iteration maps the
+/// node label and neighbour signatures of the previous iteration to the next
+/// id. Keeping the tables lets graphs seen later be relabeled in the same
+/// label space, see [`WlCompression::transform`].
+#[derive(Clone, Debug, Default)]
+pub struct WlCompression {
+    initial: HashMap<InitialLabel, usize>,
+    iterations: Vec<HashMap<Relabeling, usize>>,
+}
+
+impl WlCompression {
+    /// Runs categorical propagation on `graphs`, recording every label met
+    pub fn fit(graphs: &[WlGraph], num_iterations: usize) -> (Self, Vec<Array2<usize>>) {
+        let mut compression = WlCompression {
+            initial: HashMap::new(),
+            iterations: vec![HashMap::new(); num_iterations],
+        };
+        let (sequences, added) = compression.propagate(graphs);
+        compression.initial = added.initial;
+        compression.iterations = added.iterations;
+        (compression, sequences)
+    }
+
+    /// Relabels `graphs` with the recorded tables, which stay untouched
+    ///
+    /// Labels never seen while fitting get ids past the end of their table,
+    /// consistently within one call, so they match no fitted label.
+    pub fn transform(&self, graphs: &[WlGraph]) -> Vec<Array2<usize>> {
+        self.propagate(graphs).0
+    }
+
+    /// Number of relabeling steps
+    pub fn num_iterations(&self) -> usize {
+        self.iterations.len()
+    }
+
+    /// Number of labels recorded at iteration `h`, the initial labels being
+    /// iteration `0`
+    pub fn num_labels(&self, h: usize) -> usize {
+        match h {
+            0 => self.initial.len(),
+            h => self.iterations[h - 1].len(),
+        }
+    }
+
+    /// Bag-of-labels histograms of embeddings in this label space
+    ///
+    /// Returns one row per graph and one column per recorded label, the
+    /// labels of iteration `0` first, counting the nodes that carry each
+    /// label. Labels never seen while fitting have no column and are not
+    /// counted, so histograms of training and test graphs share columns.
+    pub fn histograms(&self, embeddings: &[Array2<usize>]) -> Array2<f64> {
+        let num_labels: Vec<usize> = (0..=self.num_iterations())
+            .map(|h| self.num_labels(h))
+            .collect();
+        let offsets: Vec<usize> = num_labels
+            .iter()
+            .scan(0, |offset, &count| {
+                let start = *offset;
+                *offset += count;
+                Some(start)
+            })
+            .collect();
+
+        let mut histograms = Array2::zeros((embeddings.len(), num_labels.iter().sum()));
+        for (mut histogram, embedding) in histograms.outer_iter_mut().zip(embeddings) {
+            for node in embedding.outer_iter() {
+                for (h, &label) in node.iter().enumerate().take(num_labels.len()) {
+                    if label < num_labels[h] {
+                        histogram[offsets[h] + label] += 1.0;
+                    }
+                }
+            }
+        }
+        histograms
+    }
+
+    /// Propagates with the recorded tables, returning the sequences and the
+    /// entries of the labels they lack
+    fn propagate(&self, graphs: &[WlGraph]) -> (Vec<Array2<usize>>, WlCompression) {
+        let num_iterations = self.num_iterations();
+        let mut added = WlCompression {
+            initial: HashMap::new(),
+            iterations: vec![HashMap::new(); num_iterations],
+        };
+
+        // Initial labels keep degrees and the unknown label apart from
+        // interned ids, so a degree of `2` never collides with label id `2`
+        let mut labels: Vec<Vec<usize>> = graphs
+            .iter()
+            .map(|graph| {
+                graph
+                    .labels()
+                    .iter()
+                    .map(|&initial| compress(&self.initial, &mut added.initial, initial))
+                    .collect()
+            })
+            .collect();
+
+        let mut sequences: Vec<Array2<usize>> = labels
+            .iter()
+            .map(|current| {
+                let mut sequence = Array2::zeros((current.len(), num_iterations + 1));
+                sequence.column_mut(0).assign(&ArrayView1::from(current));
+                sequence
+            })
+            .collect();
+
+        for iteration in 1..=num_iterations {
+            let fitted = &self.iterations[iteration - 1];
+            let added = &mut added.iterations[iteration - 1];
+            for (graph_index, graph) in graphs.iter().enumerate() {
+                let current = &labels[graph_index];
+                let relabeled: Vec<usize> = (0..graph.node_count())
+                    .map(|node| {
+                        let outgoing =
+                            signature(graph.neighbors(node), graph.edge_labels(node), current);
+                        let incoming = signature(
+                            graph.in_neighbors(node),
+                            graph.in_edge_labels(node),
+                            current,
+                        );
+                        compress(fitted, added, (current[node], outgoing, incoming))
+                    })
+                    .collect();
+
+                for (node, label) in relabeled.iter().enumerate() {
+                    sequences[graph_index][[node, iteration]] = *label;
+                }
+                labels[graph_index] = relabeled;
+            }
+        }
+
+        (sequences, added)
+    }
+}
+
+/// Id of `key` in `fitted`, or in `added`, where new keys get the next id
+/// past both tables
+fn compress<K: Hash + Eq>(
+    fitted: &HashMap<K, usize>,
+    added: &mut HashMap<K, usize>,
+    key: K,
+) -> usize {
+    if let Some(&id) = fitted.get(&key) {
+        return id;
+    }
+    let next = fitted.len() + added.len();
+    *added.entry(key).or_insert(next)
+}
+
+/// Computes WL label sequences for labeled graphs (categorical propagation)
+///
+/// Returns one `node_count × (num_iterations + 1)` matrix per graph. Column
+/// `h` holds the compressed label of every node after `h` relabeling steps.
+/// Labels are compressed with a dictionary shared by the whole collection, so
+/// equal ids in different graphs denote the same rooted subtree. Neighbours
+/// are keyed by the label of the connecting edge, if any, and directed graphs
+/// keep in- and out-neighbours apart.
+pub fn categorical_propagation(graphs: &[WlGraph], num_iterations: usize) -> Vec<Array2<usize>> {
+    WlCompression::fit(graphs, num_iterations).1
+}
+
+/// Computes WL feature sequences for attributed graphs (continuous propagation)
+///
+/// `node_features[i]` holds one `dims`-dimensional feature row per node of
+/// graph `i`. At every iteration each node feature is replaced by the average
+/// of itself and the mean of its neighbours, weighted by edge strength, and
+/// the features of all iterations are concatenated, giving one
+/// `node_count × dims * (num_iterations + 1)` matrix per graph. In directed
+/// graphs, in- and out-neighbours are averaged alike.
+pub fn continuous_propagation(
+    graphs: &[WlGraph],
+    node_features: &[Array2<f64>],
+    num_iterations: usize,
+) -> Vec<Array2<f64>> {
+    graphs
+        .iter()
+        .zip(node_features)
+        .map(|(graph, features)| propagate_features(graph, features.clone(), num_iterations))
+        .collect()
+}
+
+/// Runs continuous propagation on a `node_count × dims` feature matrix
+fn propagate_features(
+    graph: &WlGraph,
+    features: Array2<f64>,
+    num_iterations: usize,
+) -> Array2<f64> {
+    let (num_nodes, dims) = features.dim();
+    let mut sequence = Array2::zeros((num_nodes, dims * (num_iterations + 1)));
+    sequence.slice_mut(s![.., ..dims]).assign(&features);
+
+    let mut current = features;
+    for iteration in 1..=num_iterations {
+        let mut next = Array2::zeros((num_nodes, dims));
+        for node in 0..num_nodes {
+            let mut row = next.row_mut(node);
+            let mut total = 0.0;
+            let outgoing = graph.neighbors(node).iter().zip(graph.edge_weights(node));
+            let incoming = graph
+                .in_neighbors(node)
+                .iter()
+                .zip(graph.in_edge_weights(node));
+            for (&neighbor, &weight) in outgoing.chain(incoming) {
+                row.scaled_add(weight, &current.row(neighbor));
+                total += weight;
+            }
+            // Isolated nodes, and nodes whose edges all have zero strength,
+            // keep a divisor of one, halving their feature
+            if total > 0.0 {
+                row /= total;
+            }
+            row += &current.row(node);
+            row *= 0.5;
+        }
+
+        let offset = iteration * dims;
+        sequence
+            .slice_mut(s![.., offset..offset + dims])
+            .assign(&next);
+        current = next;
+    }
+
+    sequence
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::{intern_graphs, LabelDictionary, LabeledGraphInput, MissingLabelStrategy};
+    use crate::{DiGraphType, GraphType};
+    use petgraph::Graph;
+
+    fn path(labels: &[Option<i32>]) -> WlGraph {
+        let mut graph: GraphType = Graph::new_undirected();
+        let nodes: Vec<_> = labels.iter().map(|&l| graph.add_node(l)).collect();
+        for pair in nodes.windows(2) {
+            graph.add_edge(pair[0], pair[1], ());
+        }
+        // Identity-like interning: label `l` gets id `l` for small labels
+        let mut dictionary = LabelDictionary::new();
+        for label in 0..8 {
+            dictionary.intern(label);
+        }
+        graph
+            .to_labeled_wl_graph(
+                &mut dictionary,
+                &mut LabelDictionary::new(),
+                &MissingLabelStrategy::Degree,
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn test_histograms() {
+        let graphs = vec![
+            path(&[Some(1), Some(2)]),
+            path(&[Some(1), Some(2), Some(3)]),
+        ];
+        let (compression, sequences) = WlCompression::fit(&graphs, 1);
+        assert_eq!(compression.num_labels(0), 3);
+        assert_eq!(compression.num_labels(1), 4);
+
+        let histograms = compression.histograms(&sequences);
+        assert_eq!(
+            histograms,
+            ndarray::array![
+                [1.0, 1.0, 0.0, 1.0, 1.0, 0.0, 0.0],
+                [1.0, 1.0, 1.0, 1.0, 0.0, 1.0, 1.0]
+            ]
+        );
+
+        // Unseen labels are not counted
+        let unseen = compression.transform(&[path(&[Some(1), Some(7)])]);
+        let histogram = compression.histograms(&unseen);
+        assert_eq!(histogram.row(0).sum(), 1.0);
+        assert_eq!(histogram[[0, 0]], 1.0);
+    }
+
+    #[test]
+    fn test_categorical_shape_and_shared_dictionary() {
+        let graphs = vec![
+            path(&[Some(1), Some(2)]),
+            path(&[Some(1), Some(2), Some(3)]),
+        ];
+        let sequences = categorical_propagation(&graphs, 3);
+
+        assert_eq!(sequences[0].dim(), (2, 4));
+        assert_eq!(sequences[1].dim(), (3, 4));
+
+        // Initial labels are compressed in order of appearance
+        assert_eq!(sequences[0].column(0).to_vec(), vec![0, 1]);
+        assert_eq!(sequences[1].column(0).to_vec(), vec![0, 1, 2]);
+
+        // Node 0 sees the same neighbourhood in both graphs after one step
+        assert_eq!(sequences[0][[0, 1]], sequences[1][[0, 1]]);
+        // Node 1 has an extra neighbour labeled 3 in the second graph
+        assert_ne!(sequences[0][[1, 1]], sequences[1][[1, 1]]);
+    }
+
+    #[test]
+    fn test_unlabeled_graphs_use_degree() {
+        let graphs = vec![path(&[None, None, None])];
+        let sequences = categorical_propagation(&graphs, 1);
+
+        // End points share degree 1, the centre has degree 2
+        assert_eq!(sequences[0].column(0).to_vec(), vec![0, 1, 0]);
+        assert_eq!(sequences[0].column(1).to_vec(), vec![0, 1, 0]);
+    }
+
+    #[test]
+    fn test_edge_labels_split_neighbourhoods() {
+        let bonded = |orders: [u8; 2]| {
+            let mut graph = Graph::new_undirected();
+            let c = graph.add_node(6);
+            let o = graph.add_node(8);
+            let n = graph.add_node(7);
+            graph.add_edge(c, o, orders[0]);
+            graph.add_edge(c, n, orders[1]);
+            graph
+        };
+        let (graphs, _) = intern_graphs(
+            &[bonded([1, 1]), bonded([2, 1]), bonded([1, 1])],
+            &MissingLabelStrategy::Error,
+        )
+        .unwrap();
+        let sequences = categorical_propagation(&graphs, 1);
+
+        // Same node labels, but the carbon sees its oxygen through a double bond
+        assert_ne!(sequences[0][[0, 1]], sequences[1][[0, 1]]);
+        assert_ne!(sequences[0][[1, 1]], sequences[1][[1, 1]]);
+        assert_eq!(sequences[0][[2, 1]], sequences[1][[2, 1]]);
+        assert_eq!(sequences[0], sequences[2]);
+    }
+
+    #[test]
+    fn test_directed_keeps_directions_apart() {
+        // 0 -> 1 -> 2 and its reverse share every undirected neighbourhood
+        let chain = |reverse: bool| {
+            let mut graph: DiGraphType = Graph::new();
+            let nodes: Vec<_> = (0..3).map(|_| graph.add_node(Some(0))).collect();
+            for pair in nodes.windows(2) {
+                if reverse {
+                    graph.add_edge(pair[1], pair[0], ());
+                } else {
+                    graph.add_edge(pair[0], pair[1], ());
+                }
+            }
+            graph
+        };
+        let (graphs, _) =
+            intern_graphs(&[chain(false), chain(true)], &MissingLabelStrategy::Error).unwrap();
+        assert!(graphs[0].is_directed());
+        assert_eq!(graphs[0].in_neighbors(1), &[0]);
+
+        let sequences = categorical_propagation(&graphs, 1);
+        // The source of one chain is the sink of the other
+        assert_eq!(sequences[0][[0, 1]], sequences[1][[2, 1]]);
+        assert_ne!(sequences[0][[0, 1]], sequences[0][[2, 1]]);
+        assert_eq!(sequences[0][[1, 1]], sequences[1][[1, 1]]);
+    }
+
+    #[test]
+    fn test_continuous_averages_neighbours() {
+        let graphs = vec![path(&[None, None, None]), path(&[None])];
+        let features = vec![ndarray::array![[1.0], [2.0], [4.0]], ndarray::array![[3.0]]];
+        let sequences = continuous_propagation(&graphs, &features, 2);
+
+        assert_eq!(sequences[0].dim(), (3, 3));
+        assert_eq!(sequences[1].dim(), (1, 3));
+
+        assert_eq!(sequences[0].column(0).to_vec(), vec![1.0, 2.0, 4.0]);
+        assert_eq!(sequences[0].column(1).to_vec(), vec![1.5, 2.25, 3.0]);
+        assert_eq!(sequences[0].column(2).to_vec(), vec![1.875, 2.25, 2.625]);
+
+        // An isolated node is halved at every step
+        assert_eq!(sequences[1].row(0).to_vec(), vec![3.0, 1.5, 0.75]);
+    }
+
+    #[test]
+    fn test_continuous_weighted_mean() {
+        let mut graph = Graph::<(), f64, _>::new_undirected();
+        let centre = graph.add_node(());
+        let light = graph.add_node(());
+        let heavy = graph.add_node(());
+        graph.add_edge(centre, light, 1.0);
+        graph.add_edge(centre, heavy, 3.0);
+
+        let graphs = vec![WlGraph::from_weighted(&graph)];
+        let features = vec![ndarray::array![[0.0], [4.0], [8.0]]];
+        let sequences = continuous_propagation(&graphs, &features, 1);
+
+        // (1 * 4 + 3 * 8) / 4 = 7 for the centre, single neighbours as before
+        assert_eq!(sequences[0].column(1).to_vec(), vec![3.5, 2.0, 4.0]);
+    }
+
+    #[test]
+    fn test_continuous_multi_dimensional() {
+        let graphs = vec![path(&[None, None])];
+        let features = vec![ndarray::array![[1.0, 10.0], [3.0, 30.0]]];
+        let sequences = continuous_propagation(&graphs, &features, 1);
+
+        // Dimensions of each iteration stay adjacent: [x, y, x', y']
+        assert_eq!(sequences[0].row(0).to_vec(), vec![1.0, 10.0, 2.0, 20.0]);
+        assert_eq!(sequences[0].row(1).to_vec(), vec![3.0, 30.0, 2.0, 20.0]);
+    }
+}
@@ -0,0 +1,525 @@
+//! Optimal transport between node embeddings
+//!
+//! Native solvers for the Wasserstein stage of WWL. Both graphs put uniform
+//! mass on their nodes and the ground cost between two nodes is computed
+//! from their WL embeddings.
+
+use ndarray::{Array1, Array2, ArrayView1, Axis};
+use petgraph::graph::NodeIndex;
+
+use crate::{WlGraph, WwlError};
+
+/// Solution of a transport problem between two node sets
+#[derive(Clone, Debug)]
+pub struct Transport {
+    /// Total transport cost, i.e. the Wasserstein distance
+    pub distance: f64,
+    /// Coupling with one row per source node and one column per target node
+    pub plan: Array2<f64>,
+}
+
+impl Transport {
+    /// Maps the non-zero entries of the plan to the nodes of the two graphs
+    /// it was computed for
+    pub fn alignment(&self, source: &WlGraph, target: &WlGraph) -> Alignment {
+        let couplings = self
+            .plan
+            .indexed_iter()
+            .filter(|(_, &mass)| mass > 0.0)
+            .map(|((i, j), &mass)| Coupling {
+                source: NodeIndex::new(source.node_index(i)),
+                target: NodeIndex::new(target.node_index(j)),
+                mass,
+            })
+            .collect();
+        Alignment {
+            distance: self.distance,
+            couplings,
+        }
+    }
+}
+
+/// Mass moved from a node of the first graph to a node of the second
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Coupling {
+    pub source: NodeIndex,
+    pub target: NodeIndex,
+    pub mass: f64,
+}
+
+/// Sparse transport plan between the nodes of two graphs
+///
+/// Nodes are identified by their petgraph index, see
+/// [`WlGraph::node_index`]. Masses sum to one; each node of a graph with `n`
+/// nodes sends or receives `1 / n` in total.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Alignment {
+    /// Wasserstein distance of the pair
+    pub distance: f64,
+    /// Non-zero entries of the plan, in source then target order
+    pub couplings: Vec<Coupling>,
+}
+
+impl Alignment {
+    /// Couplings leaving `source`
+    pub fn couplings_from(&self, source: NodeIndex) -> impl Iterator<Item = &Coupling> {
+        self.couplings
+            .iter()
+            .filter(move |coupling| coupling.source == source)
+    }
+}
+
+/// Ground cost for categorical embeddings: the fraction of differing labels
+pub fn hamming_cost<T: PartialEq>(source: &Array2<T>, target: &Array2<T>) -> Array2<f64> {
+    pairwise_cost(source, target, |a, b| {
+        let differing = a.iter().zip(b).filter(|(x, y)| x != y).count();
+        differing as f64 / a.len().max(1) as f64
+    })
+}
+
+/// Ground cost for continuous embeddings: the euclidean distance
+pub fn euclidean_cost(source: &Array2<f64>, target: &Array2<f64>) -> Array2<f64> {
+    pairwise_cost(source, target, |a, b| {
+        a.iter()
+            .zip(b)
+            .map(|(x, y)| (x - y) * (x - y))
+            .sum::<f64>()
+            .sqrt()
+    })
+}
+
+fn pairwise_cost<T>(
+    source: &Array2<T>,
+    target: &Array2<T>,
+    cost: impl Fn(ArrayView1<T>, ArrayView1<T>) -> f64,
+) -> Array2<f64> {
+    Array2::from_shape_fn((source.nrows(), target.nrows()), |(i, j)| {
+        cost(source.row(i), target.row(j))
+    })
+}
+
+/// Solves the exact earth mover's distance with uniform node masses
+///
+/// `cost` has one row per source node and one column per target node. Masses
+/// are scaled to integers (`m` units per source, `n` units per target) so the
+/// successive shortest path solver below works in exact arithmetic; the plan
+/// is rescaled to unit total mass afterwards.
+pub fn emd(cost: &Array2<f64>) -> Transport {
+    let (n, m) = cost.dim();
+    if n == 0 || m == 0 {
+        return Transport {
+            distance: 0.0,
+            plan: Array2::zeros((n, m)),
+        };
+    }
+
+    let mut solver = FlowSolver::new(cost, vec![m as u64; n], vec![n as u64; m]);
+    solver.solve();
+
+    let total = (n * m) as f64;
+    let plan = solver.flow.mapv(|f| f as f64 / total);
+    let distance = (&plan * cost).sum();
+    Transport { distance, plan }
+}
+
+/// Parameters of the entropic Sinkhorn solver
+///
+/// The defaults follow `ot.sinkhorn` of POT. A pair that stops at
+/// `max_iterations` keeps its last iterate, unless `require_convergence` is
+/// set.
+#[derive(Clone, Debug)]
+pub struct SinkhornConfig {
+    /// Entropic regularisation strength
+    pub epsilon: f64,
+    pub max_iterations: usize,
+    /// Stop once the target marginal is matched up to this L1 error
+    pub tolerance: f64,
+    /// Iterate on log-scaled potentials, which avoids underflow of
+    /// `exp(-cost / epsilon)` for small `epsilon` or large costs
+    pub log_domain: bool,
+    /// Fail the native backend with [`WwlError::NotConverged`] as soon as one
+    /// pair stops at `max_iterations` with a marginal error above `tolerance`
+    pub require_convergence: bool,
+}
+
+impl Default for SinkhornConfig {
+    fn default() -> Self {
+        Self {
+            epsilon: 1e-2,
+            max_iterations: 1000,
+            tolerance: 1e-9,
+            log_domain: true,
+            require_convergence: false,
+        }
+    }
+}
+
+impl SinkhornConfig {
+    /// Checks that the solver parameters describe a well-posed problem
+    pub fn validate(&self) -> Result<(), WwlError> {
+        if !(self.epsilon.is_finite() && self.epsilon > 0.0) {
+            return Err(WwlError::InvalidConfig(format!(
+                "Sinkhorn epsilon must be positive and finite, got {}",
+                self.epsilon
+            )));
+        }
+        if self.max_iterations == 0 {
+            return Err(WwlError::InvalidConfig(
+                "Sinkhorn max_iterations must be at least 1".to_string(),
+            ));
+        }
+        if self.tolerance.is_nan() || self.tolerance < 0.0 {
+            return Err(WwlError::InvalidConfig(format!(
+                "Sinkhorn tolerance must be non-negative, got {}",
+                self.tolerance
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Convergence report of a Sinkhorn run
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Convergence {
+    pub iterations: usize,
+    /// L1 error on the target marginal after the last iteration
+    pub error: f64,
+    pub converged: bool,
+}
+
+/// Solves the entropy regularised transport problem with uniform node masses
+///
+/// The reported distance is `<plan, cost>`, without the entropy term. A run
+/// that stops at `max_iterations` still returns its current plan; check the
+/// returned [`Convergence`] before trusting it.
+pub fn sinkhorn(cost: &Array2<f64>, config: &SinkhornConfig) -> (Transport, Convergence) {
+    let (n, m) = cost.dim();
+    if n == 0 || m == 0 {
+        let transport = Transport {
+            distance: 0.0,
+            plan: Array2::zeros((n, m)),
+        };
+        let convergence = Convergence {
+            iterations: 0,
+            error: 0.0,
+            converged: true,
+        };
+        return (transport, convergence);
+    }
+
+    let a = Array1::from_elem(n, 1.0 / n as f64);
+    let b = Array1::from_elem(m, 1.0 / m as f64);
+    let (plan, convergence) = if config.log_domain {
+        sinkhorn_log(cost, &a, &b, config)
+    } else {
+        sinkhorn_scaling(cost, &a, &b, config)
+    };
+
+    let distance = (&plan * cost).sum();
+    (Transport { distance, plan }, convergence)
+}
+
+/// Classic Sinkhorn-Knopp matrix scaling
+fn sinkhorn_scaling(
+    cost: &Array2<f64>,
+    a: &Array1<f64>,
+    b: &Array1<f64>,
+    config: &SinkhornConfig,
+) -> (Array2<f64>, Convergence) {
+    let kernel = cost.mapv(|c| (-c / config.epsilon).exp());
+    let mut u = Array1::from_elem(a.len(), 1.0);
+    let mut v = Array1::from_elem(b.len(), 1.0);
+
+    let mut convergence = Convergence {
+        iterations: 0,
+        error: f64::INFINITY,
+        converged: false,
+    };
+    while convergence.iterations < config.max_iterations {
+        v = b / &kernel.t().dot(&u);
+        u = a / &kernel.dot(&v);
+        convergence.iterations += 1;
+
+        if u.iter().chain(v.iter()).any(|x| !x.is_finite()) {
+            // Underflow in the kernel; the log domain variant handles this
+            break;
+        }
+        let marginal = &v * &kernel.t().dot(&u);
+        convergence.error = (&marginal - b).mapv(f64::abs).sum();
+        if convergence.error < config.tolerance {
+            convergence.converged = true;
+            break;
+        }
+    }
+
+    let plan = Array2::from_shape_fn(kernel.dim(), |(i, j)| u[i] * kernel[[i, j]] * v[j]);
+    (plan, convergence)
+}
+
+/// Sinkhorn iterations on the dual potentials `f` and `g`
+fn sinkhorn_log(
+    cost: &Array2<f64>,
+    a: &Array1<f64>,
+    b: &Array1<f64>,
+    config: &SinkhornConfig,
+) -> (Array2<f64>, Convergence) {
+    let epsilon = config.epsilon;
+    let (n, m) = cost.dim();
+    let mut f = Array1::zeros(n);
+    let mut g = Array1::zeros(m);
+    let log_plan = |f: &Array1<f64>, g: &Array1<f64>| {
+        Array2::from_shape_fn((n, m), |(i, j)| (f[i] + g[j] - cost[[i, j]]) / epsilon)
+    };
+
+    let mut convergence = Convergence {
+        iterations: 0,
+        error: f64::INFINITY,
+        converged: false,
+    };
+    while convergence.iterations < config.max_iterations {
+        let log = log_plan(&f, &g);
+        for (j, column) in log.columns().into_iter().enumerate() {
+            g[j] += epsilon * (b[j].ln() - log_sum_exp(column));
+        }
+        let log = log_plan(&f, &g);
+        for (i, row) in log.rows().into_iter().enumerate() {
+            f[i] += epsilon * (a[i].ln() - log_sum_exp(row));
+        }
+        convergence.iterations += 1;
+
+        let marginal = log_plan(&f, &g).mapv(f64::exp).sum_axis(Axis(0));
+        convergence.error = (&marginal - b).mapv(f64::abs).sum();
+        if convergence.error < config.tolerance {
+            convergence.converged = true;
+            break;
+        }
+    }
+
+    (log_plan(&f, &g).mapv(f64::exp), convergence)
+}
+
+fn log_sum_exp(values: ArrayView1<f64>) -> f64 {
+    let max = values.fold(f64::NEG_INFINITY, |a, &x| a.max(x));
+    if max == f64::NEG_INFINITY {
+        return max;
+    }
+    max + values.mapv(|x| (x - max).exp()).sum().ln()
+}
+
+/// Min-cost flow on the complete bipartite graph between sources and targets
+///
+/// Node layout: sources `0..n`, targets `n..n + m`, then the super source and
+/// the super sink. Potentials keep every reduced cost non-negative so each
+/// augmenting path can be found with a dense Dijkstra pass.
+struct FlowSolver<'a> {
+    cost: &'a Array2<f64>,
+    supply: Vec<u64>,
+    demand: Vec<u64>,
+    flow: Array2<u64>,
+    potential: Vec<f64>,
+}
+
+impl<'a> FlowSolver<'a> {
+    fn new(cost: &'a Array2<f64>, supply: Vec<u64>, demand: Vec<u64>) -> Self {
+        let (n, m) = cost.dim();
+        let mut potential = vec![0.0; n + m + 2];
+        for j in 0..m {
+            potential[n + j] = cost.column(j).fold(f64::INFINITY, |a, &c| a.min(c));
+        }
+        potential[n + m + 1] = potential[n..n + m]
+            .iter()
+            .fold(f64::INFINITY, |a, &p| a.min(p));
+
+        Self {
+            cost,
+            supply,
+            demand,
+            flow: Array2::zeros((n, m)),
+            potential,
+        }
+    }
+
+    fn solve(&mut self) {
+        let (n, m) = self.cost.dim();
+        let (source, sink) = (n + m, n + m + 1);
+        let nodes = n + m + 2;
+
+        while self.supply.iter().any(|&s| s > 0) {
+            let mut dist = vec![f64::INFINITY; nodes];
+            let mut prev = vec![usize::MAX; nodes];
+            let mut done = vec![false; nodes];
+            dist[source] = 0.0;
+
+            loop {
+                let next = (0..nodes)
+                    .filter(|&v| !done[v] && dist[v].is_finite())
+                    .min_by(|&a, &b| dist[a].total_cmp(&dist[b]));
+                let Some(u) = next else { break };
+                done[u] = true;
+                if u == sink {
+                    break;
+                }
+
+                let relax = |v: usize, cost: f64, dist: &mut [f64], prev: &mut [usize]| {
+                    let reduced = (cost + self.potential[u] - self.potential[v]).max(0.0);
+                    if dist[u] + reduced < dist[v] {
+                        dist[v] = dist[u] + reduced;
+                        prev[v] = u;
+                    }
+                };
+
+                if u == source {
+                    for i in (0..n).filter(|&i| self.supply[i] > 0) {
+                        relax(i, 0.0, &mut dist, &mut prev);
+                    }
+                } else if u < n {
+                    for j in 0..m {
+                        relax(n + j, self.cost[[u, j]], &mut dist, &mut prev);
+                    }
+                } else {
+                    let j = u - n;
+                    for i in (0..n).filter(|&i| self.flow[[i, j]] > 0) {
+                        relax(i, -self.cost[[i, j]], &mut dist, &mut prev);
+                    }
+                    if self.demand[j] > 0 {
+                        relax(sink, 0.0, &mut dist, &mut prev);
+                    }
+                }
+            }
+
+            let reached = dist[sink];
+            debug_assert!(reached.is_finite(), "balanced problem always has a path");
+            for (potential, d) in self.potential.iter_mut().zip(&dist) {
+                *potential += d.min(reached);
+            }
+
+            let path = self.trace(&prev, sink);
+            let first = path[1];
+            let last = path[path.len() - 2] - n;
+            let mut amount = self.supply[first].min(self.demand[last]);
+            for pair in path[1..path.len() - 1].windows(2) {
+                if pair[0] >= n {
+                    // Backward edge from a target to a source cancels flow
+                    amount = amount.min(self.flow[[pair[1], pair[0] - n]]);
+                }
+            }
+
+            self.supply[first] -= amount;
+            self.demand[last] -= amount;
+            for pair in path[1..path.len() - 1].windows(2) {
+                if pair[0] < n {
+                    self.flow[[pair[0], pair[1] - n]] += amount;
+                } else {
+                    self.flow[[pair[1], pair[0] - n]] -= amount;
+                }
+            }
+        }
+    }
+
+    fn trace(&self, prev: &[usize], sink: usize) -> Vec<usize> {
+        let mut path = vec![sink];
+        let mut node = sink;
+        while prev[node] != usize::MAX {
+            node = prev[node];
+            path.push(node);
+        }
+        path.reverse();
+        path
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::array;
+
+    #[test]
+    fn test_emd_assignment() {
+        let cost = array![[0.0, 1.0], [1.0, 0.0]];
+        let transport = emd(&cost);
+
+        assert_eq!(transport.distance, 0.0);
+        assert_eq!(transport.plan, array![[0.5, 0.0], [0.0, 0.5]]);
+    }
+
+    #[test]
+    fn test_emd_unequal_sizes() {
+        // Two sources, three targets: every target receives a third of the
+        // mass, and the middle target has to be shared between both sources
+        let cost = array![[0.0, 1.0, 3.0], [3.0, 1.0, 0.0]];
+        let transport = emd(&cost);
+
+        assert!((transport.distance - 1.0 / 3.0).abs() < 1e-12);
+        for row in transport.plan.rows() {
+            assert!((row.sum() - 0.5).abs() < 1e-12);
+        }
+        for column in transport.plan.columns() {
+            assert!((column.sum() - 1.0 / 3.0).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_emd_requires_rerouting() {
+        // The greedy choice (0 -> 0) must be undone to reach the optimum
+        let cost = array![[1.0, 2.0], [1.0, 10.0]];
+        let transport = emd(&cost);
+
+        assert!((transport.distance - 1.5).abs() < 1e-12);
+        assert_eq!(transport.plan, array![[0.0, 0.5], [0.5, 0.0]]);
+    }
+
+    #[test]
+    fn test_sinkhorn_approaches_emd() {
+        let cost = array![[0.0, 1.0, 3.0], [3.0, 1.0, 0.0]];
+        let exact = emd(&cost);
+
+        for log_domain in [true, false] {
+            let config = SinkhornConfig {
+                epsilon: 0.05,
+                max_iterations: 1000,
+                log_domain,
+                ..Default::default()
+            };
+            let (transport, convergence) = sinkhorn(&cost, &config);
+
+            assert!(convergence.converged);
+            assert!(convergence.error < config.tolerance);
+            assert!((transport.distance - exact.distance).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn test_sinkhorn_reports_non_convergence() {
+        let cost = array![[0.0, 50.0], [50.0, 0.0], [25.0, 25.0]];
+
+        // exp(-50 / 0.01) underflows, so plain scaling cannot make progress
+        let config = SinkhornConfig {
+            log_domain: false,
+            ..Default::default()
+        };
+        let (_, convergence) = sinkhorn(&cost, &config);
+        assert!(!convergence.converged);
+
+        let config = SinkhornConfig {
+            max_iterations: 1000,
+            ..Default::default()
+        };
+        let (transport, convergence) = sinkhorn(&cost, &config);
+        assert!(convergence.converged);
+        assert!(transport.distance.is_finite());
+    }
+
+    #[test]
+    fn test_ground_costs() {
+        let labels_a = array![[0, 1, 2], [0, 1, 3]];
+        let labels_b = array![[0, 1, 2]];
+        assert_eq!(
+            hamming_cost(&labels_a, &labels_b),
+            array![[0.0], [1.0 / 3.0]]
+        );
+
+        let features_a = array![[0.0, 0.0]];
+        let features_b = array![[3.0, 4.0]];
+        assert_eq!(euclidean_cost(&features_a, &features_b), array![[5.0]]);
+    }
+}